@@ -0,0 +1,4 @@
+pub mod model;
+pub mod parser;
+pub mod sampler;
+pub mod stationary;
@@ -0,0 +1,683 @@
+use crate::parser;
+use rand::seq::index::sample;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Format of the file the source model is read from
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Guess the format from the file extension, falling back to `matrix`
+    Auto,
+    /// Bare whitespace separated probability matrix, symbols are picked at random
+    Matrix,
+    Json,
+    Toml,
+    Csv,
+    /// Sample text, a model of the requested order is trained from its characters,
+    /// never guessed from the extension
+    Text,
+}
+
+impl Format {
+    /// Resolves `Auto` by the extension of `path`
+    pub fn resolve(self, path: &Path) -> Self {
+        match self {
+            Format::Auto => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => Format::Json,
+                Some("toml") => Format::Toml,
+                Some("csv") => Format::Csv,
+                _ => Format::Matrix,
+            },
+            format => format,
+        }
+    }
+}
+
+/// Source model with every probability tied to an explicit symbol.
+///
+/// `probabilities` describes a memoryless source, `transitions[i][j]` is the
+/// probability of `symbols[j]` following `symbols[i]` in a Markov source.
+/// `contexts` describe a Markov source of order k, where every listed
+/// context of k symbols has a distribution of its own and unlisted
+/// contexts fall back to `probabilities`.
+/// A model file may contain `probabilities` together with either
+/// `transitions` or `contexts`. Next to `transitions` they are only the
+/// symbol frequencies, as written by the trainer, and the Generator does not
+/// use them: a Markov source starts from the stationary distribution of its
+/// transitions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Model {
+    pub symbols: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probabilities: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transitions: Option<Vec<Vec<f64>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<Vec<Context>>,
+}
+
+/// Distribution of the next symbol after the symbols of `context`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    pub context: Vec<String>,
+    pub probabilities: Vec<f64>,
+}
+
+impl Model {
+    /// Reads a model in the given format, `path` is only used to guess the format.
+    /// The symbols of a bare matrix are picked from the first `range` characters,
+    /// a sample text is trained into a model of the given `order`.
+    pub fn load<R: Rng>(
+        path: &Path,
+        text: &str,
+        format: Format,
+        range: usize,
+        order: usize,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        let model = match format.resolve(path) {
+            Format::Text => Self::train(text, order)?,
+            Format::Matrix | Format::Auto => Self::from_matrix(text, range, rng)?,
+            format => Self::from_structured(text, format)?,
+        };
+        model.validate()?;
+        Ok(model)
+    }
+
+    /// Reads a model file that names its symbols, i.e. JSON, TOML or CSV
+    /// picked by the extension of `path`
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        let model = Self::from_structured(&text, Format::Auto.resolve(path))?;
+        model.validate()?;
+        Ok(model)
+    }
+
+    fn from_structured(text: &str, format: Format) -> Result<Self, Error> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        match format {
+            Format::Json => serde_json::from_str(text).map_err(|err| invalid(err.to_string())),
+            Format::Toml => toml::from_str(text).map_err(|err| invalid(err.to_string())),
+            Format::Csv => Self::from_csv(text),
+            _ => Err(invalid(format!(
+                "{:?} does not name the symbols of a model",
+                format
+            ))),
+        }
+    }
+
+    /// Reads a bare probability matrix and assigns it distinct printable symbols
+    /// picked from the first `range` characters
+    pub fn from_matrix<R: Rng>(text: &str, range: usize, rng: &mut R) -> Result<Self, Error> {
+        let mut matrix = parser::parse_matrix(text)?;
+        let candidates = (0..range as u32)
+            .filter_map(char::from_u32)
+            .filter(|c| !c.is_control() && !c.is_whitespace())
+            .collect::<Vec<_>>();
+        let count = matrix[0].len();
+        if candidates.len() < count {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "range {} has only {} printable characters, {} symbols needed",
+                    range,
+                    candidates.len(),
+                    count
+                ),
+            ));
+        }
+        let symbols = sample(rng, candidates.len(), count)
+            .into_iter()
+            .map(|index| candidates[index].to_string())
+            .collect();
+        Ok(if matrix.len() == 1 {
+            Model {
+                symbols,
+                probabilities: matrix.pop(),
+                transitions: None,
+                contexts: None,
+            }
+        } else {
+            Model {
+                symbols,
+                probabilities: None,
+                transitions: Some(matrix),
+                contexts: None,
+            }
+        })
+    }
+
+    /// Trains a model of the given order from the characters of a sample text.
+    ///
+    /// The character frequencies become `probabilities`, for a positive order
+    /// every context of `order` characters found in the text gets the
+    /// distribution of the characters that followed it.
+    pub fn train(text: &str, order: usize) -> Result<Self, Error> {
+        let letters = text.chars().collect::<Vec<_>>();
+        if letters.len() <= order {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "sample of {} characters is too short for order {}",
+                    letters.len(),
+                    order
+                ),
+            ));
+        }
+        let mut alphabet = letters.clone();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        let index = alphabet
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (c, i))
+            .collect::<HashMap<_, _>>();
+        let sequence = letters.iter().map(|c| index[c]).collect::<Vec<_>>();
+
+        let mut counts = vec![0usize; alphabet.len()];
+        for &letter in &sequence {
+            counts[letter] += 1;
+        }
+        // BTreeMap keeps the contexts of the written model sorted
+        let mut context_counts = BTreeMap::<&[usize], Vec<usize>>::new();
+        if order > 0 {
+            for window in sequence.windows(order + 1) {
+                let next = context_counts
+                    .entry(&window[..order])
+                    .or_insert_with(|| vec![0; alphabet.len()]);
+                next[window[order]] += 1;
+            }
+        }
+        let symbols = alphabet.iter().map(char::to_string).collect::<Vec<_>>();
+        let contexts = context_counts
+            .into_iter()
+            .map(|(context, next)| Context {
+                context: context.iter().map(|&i| symbols[i].clone()).collect(),
+                probabilities: normalize(&next),
+            })
+            .collect::<Vec<_>>();
+        Ok(Model {
+            probabilities: Some(normalize(&counts)),
+            transitions: None,
+            contexts: if contexts.is_empty() {
+                None
+            } else {
+                Some(contexts)
+            },
+            symbols,
+        })
+    }
+
+    /// Reads a model from CSV.
+    ///
+    /// The header holds the symbols after a label column. A record labelled
+    /// `*` (or left empty) holds the memoryless probabilities, a record
+    /// labelled with a symbol holds the transitions from that symbol and
+    /// a record labelled with several whitespace separated symbols holds
+    /// the distribution after that context.
+    /// The delimiter is `;` when the header contains one, so decimal commas
+    /// can be used, and `,` otherwise.
+    pub fn from_csv(text: &str) -> Result<Self, Error> {
+        let header_line = text.lines().next().unwrap_or_default();
+        let delimiter = if header_line.contains(';') {
+            b';'
+        } else {
+            b','
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .from_reader(text.as_bytes());
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+
+        let symbols = reader
+            .headers()
+            .map_err(|err| invalid(err.to_string()))?
+            .iter()
+            .skip(1)
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let mut probabilities = None;
+        let mut rows = HashMap::new();
+        let mut contexts = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|err| invalid(err.to_string()))?;
+            let line = record.position().map_or(0, |position| position.line());
+            let label = record.get(0).unwrap_or_default().to_string();
+            let row = record
+                .iter()
+                .skip(1)
+                .enumerate()
+                .map(|(index, field)| {
+                    parser::parse_number(field).map_err(|_| {
+                        invalid(format!(
+                            "line {}, field {}: `{}` is not a number",
+                            line,
+                            index + 2,
+                            field
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if label.is_empty() || label == "*" {
+                if probabilities.replace(row).is_some() {
+                    return Err(invalid(format!(
+                        "line {}: memoryless probabilities given twice",
+                        line
+                    )));
+                }
+            } else if label.split_whitespace().nth(1).is_some() {
+                contexts.push(Context {
+                    context: label.split_whitespace().map(String::from).collect(),
+                    probabilities: row,
+                });
+            } else if rows.insert(label.clone(), row).is_some() {
+                return Err(invalid(format!(
+                    "line {}: transitions from `{}` given twice",
+                    line, label
+                )));
+            }
+        }
+
+        let transitions = if rows.is_empty() {
+            None
+        } else {
+            let matrix = symbols
+                .iter()
+                .map(|symbol| {
+                    rows.remove(symbol).ok_or_else(|| {
+                        invalid(format!("transitions from `{}` are missing", symbol))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(label) = rows.keys().next() {
+                return Err(invalid(format!(
+                    "transitions from `{}`, which is not in the header",
+                    label
+                )));
+            }
+            Some(matrix)
+        };
+        Ok(Model {
+            symbols,
+            probabilities,
+            transitions,
+            contexts: if contexts.is_empty() {
+                None
+            } else {
+                Some(contexts)
+            },
+        })
+    }
+
+    /// Writes the model in a format [`Model::load`] reads back
+    pub fn serialize(&self, format: Format) -> Result<String, Error> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+        match format {
+            Format::Json => {
+                serde_json::to_string_pretty(self).map_err(|err| invalid(err.to_string()))
+            }
+            Format::Toml => toml::to_string(self).map_err(|err| invalid(err.to_string())),
+            Format::Csv => {
+                match self.symbols.iter().find(|symbol| {
+                    symbol.contains(|c: char| c.is_whitespace() || c == ';' || c == '#')
+                }) {
+                    Some(symbol) => Err(invalid(format!(
+                        "symbol {:?} cannot be written to CSV",
+                        symbol
+                    ))),
+                    None => Ok(self.to_csv()),
+                }
+            }
+            Format::Matrix => match (&self.probabilities, &self.transitions, &self.contexts) {
+                (_, Some(rows), None) => Ok(matrix_to_string(rows)),
+                (Some(row), None, None) => Ok(matrix_to_string(std::slice::from_ref(row))),
+                _ => Err(invalid(
+                    "a bare matrix cannot hold the contexts of the model".to_string(),
+                )),
+            },
+            Format::Auto | Format::Text => {
+                Err(invalid(format!("models cannot be written as {:?}", format)))
+            }
+        }
+    }
+
+    /// Writes the model as the CSV read by [`Model::from_csv`], `;` separated
+    fn to_csv(&self) -> String {
+        let row = |label: &str, probabilities: &[f64]| {
+            std::iter::once(label.to_string())
+                .chain(probabilities.iter().map(f64::to_string))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        let mut lines = vec![std::iter::once("from")
+            .chain(self.symbols.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(";")];
+        if let Some(probabilities) = &self.probabilities {
+            lines.push(row("*", probabilities));
+        }
+        if let Some(transitions) = &self.transitions {
+            for (symbol, probabilities) in self.symbols.iter().zip(transitions) {
+                lines.push(row(symbol, probabilities));
+            }
+        }
+        for context in self.contexts.iter().flatten() {
+            lines.push(row(&context.context.join(" "), &context.probabilities));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Splits a generated text back into indices of `symbols`,
+    /// always taking the longest symbol that matches
+    pub fn tokenize(&self, text: &str) -> Result<Vec<usize>, Error> {
+        let mut by_length = self.symbols.iter().enumerate().collect::<Vec<_>>();
+        by_length.sort_by_key(|(_, symbol)| std::cmp::Reverse(symbol.len()));
+        let mut tokens = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            match by_length
+                .iter()
+                .find(|(_, symbol)| rest.starts_with(symbol.as_str()))
+            {
+                Some(&(index, symbol)) => {
+                    tokens.push(index);
+                    rest = &rest[symbol.len()..];
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "byte {}: {:?} does not start with any symbol of the model",
+                            text.len() - rest.len(),
+                            rest.chars().next().unwrap()
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Number of preceding symbols the next symbol depends on
+    pub fn order(&self) -> usize {
+        match (&self.contexts, &self.transitions) {
+            (Some(contexts), _) => contexts[0].context.len(),
+            (None, Some(_)) => 1,
+            (None, None) => 0,
+        }
+    }
+
+    /// Contexts with their symbols replaced by indices into `symbols`
+    pub fn indexed_contexts(&self) -> Vec<(Vec<usize>, &[f64])> {
+        let index = self
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| (symbol, i))
+            .collect::<HashMap<_, _>>();
+        self.contexts
+            .iter()
+            .flatten()
+            .map(|context| {
+                (
+                    context.context.iter().map(|symbol| index[symbol]).collect(),
+                    context.probabilities.as_slice(),
+                )
+            })
+            .collect()
+    }
+
+    /// Checks that the symbols are distinct and every distribution
+    /// has one entry per symbol and sums to 1
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+        if self.symbols.is_empty() {
+            return invalid("model has no symbols".to_string());
+        }
+        let mut seen = HashMap::new();
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            if symbol.is_empty() {
+                return invalid(format!("symbol {} is empty", index + 1));
+            }
+            if let Some(first) = seen.insert(symbol, index) {
+                return invalid(format!(
+                    "symbol `{}` is listed twice, at {} and {}",
+                    symbol,
+                    first + 1,
+                    index + 1
+                ));
+            }
+        }
+        if self.probabilities.is_none() && self.transitions.is_none() && self.contexts.is_none() {
+            return invalid(
+                "model has neither probabilities, transitions nor contexts".to_string(),
+            );
+        }
+        if self.transitions.is_some() && self.contexts.is_some() {
+            return invalid("model has both transitions and contexts".to_string());
+        }
+        if let Some(probabilities) = &self.probabilities {
+            self.validate_distribution("probabilities", probabilities)?;
+        }
+        if let Some(transitions) = &self.transitions {
+            if transitions.len() != self.symbols.len() {
+                return invalid(format!(
+                    "transitions have {} rows, expected {}",
+                    transitions.len(),
+                    self.symbols.len()
+                ));
+            }
+            for (symbol, row) in self.symbols.iter().zip(transitions) {
+                self.validate_distribution(&format!("transitions from `{}`", symbol), row)?;
+            }
+        }
+        if let Some(contexts) = &self.contexts {
+            self.validate_contexts(contexts, &seen)?;
+        }
+        Ok(())
+    }
+
+    fn validate_contexts(
+        &self,
+        contexts: &[Context],
+        symbols: &HashMap<&String, usize>,
+    ) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+        let order = match contexts.first() {
+            Some(first) if !first.context.is_empty() => first.context.len(),
+            _ => return invalid("contexts have to hold at least one symbol".to_string()),
+        };
+        let mut seen = HashMap::new();
+        for (index, context) in contexts.iter().enumerate() {
+            let name = format!("context `{}`", context.context.join(" "));
+            if context.context.len() != order {
+                return invalid(format!(
+                    "{} has {} symbols, expected {}",
+                    name,
+                    context.context.len(),
+                    order
+                ));
+            }
+            if let Some(unknown) = context
+                .context
+                .iter()
+                .find(|symbol| !symbols.contains_key(symbol))
+            {
+                return invalid(format!("{} has unknown symbol `{}`", name, unknown));
+            }
+            if seen.insert(&context.context, index).is_some() {
+                return invalid(format!("{} is listed twice", name));
+            }
+            self.validate_distribution(&name, &context.probabilities)?;
+        }
+        // without a fallback every possible context needs a distribution
+        let possible = self.symbols.len().checked_pow(order as u32);
+        if self.probabilities.is_none() && possible != Some(contexts.len()) {
+            return invalid(format!(
+                "{} contexts of order {} are listed, without `probabilities` to fall back to all {} are needed",
+                contexts.len(),
+                order,
+                possible.map_or_else(|| "of them".to_string(), |count| count.to_string())
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_distribution(&self, name: &str, distribution: &[f64]) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+        if distribution.len() != self.symbols.len() {
+            return invalid(format!(
+                "{} have {} entries, expected {}",
+                name,
+                distribution.len(),
+                self.symbols.len()
+            ));
+        }
+        for (symbol, &p) in self.symbols.iter().zip(distribution) {
+            if !(0.0..=1.0).contains(&p) {
+                return invalid(format!(
+                    "{}: probability {} of `{}` is not in range 0..1",
+                    name, p, symbol
+                ));
+            }
+        }
+        let sum: f64 = distribution.iter().sum();
+        if (sum - 1.0).abs() > parser::SUM_TOLERANCE {
+            return invalid(format!("{} sum to {}, expected 1", name, sum));
+        }
+        Ok(())
+    }
+}
+
+/// Turns counts into probabilities, the counts must not all be zero
+pub fn normalize(counts: &[usize]) -> Vec<f64> {
+    let total: usize = counts.iter().sum();
+    counts
+        .iter()
+        .map(|&count| count as f64 / total as f64)
+        .collect()
+}
+
+/// Writes rows of probabilities as a whitespace separated matrix
+fn matrix_to_string(rows: &[Vec<f64>]) -> String {
+    rows.iter()
+        .map(|row| row.iter().map(f64::to_string).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Context, Format, Model};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::path::Path;
+
+    fn load(name: &str, text: &str) -> std::io::Result<Model> {
+        Model::load(
+            Path::new(name),
+            text,
+            Format::Auto,
+            128,
+            0,
+            &mut StdRng::seed_from_u64(1),
+        )
+    }
+
+    fn markov() -> Model {
+        Model {
+            symbols: vec!["a".to_string(), "b".to_string(), "ch".to_string()],
+            probabilities: Some(vec![0.5, 0.25, 0.25]),
+            transitions: Some(vec![
+                vec![0.1, 0.6, 0.3],
+                vec![1.0, 0.0, 0.0],
+                vec![0.125, 0.375, 0.5],
+            ]),
+            contexts: None,
+        }
+    }
+
+    fn order_2() -> Model {
+        Model {
+            symbols: vec!["x".to_string(), "y".to_string()],
+            probabilities: Some(vec![0.75, 0.25]),
+            transitions: None,
+            contexts: Some(vec![Context {
+                context: vec!["x".to_string(), "y".to_string()],
+                probabilities: vec![0.2, 0.8],
+            }]),
+        }
+    }
+
+    #[test]
+    fn every_format_reads_back() {
+        for model in [markov(), order_2()] {
+            for (name, format) in [
+                ("model.json", Format::Json),
+                ("model.toml", Format::Toml),
+                ("model.csv", Format::Csv),
+            ] {
+                let text = model.serialize(format).unwrap();
+                assert_eq!(load(name, &text).unwrap(), model, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn csv_with_decimal_commas() {
+        let model = load("model.csv", "from;a;b\n*;0,5;1/2\na;0;1\nb;1;0\n").unwrap();
+        assert_eq!(model.probabilities, Some(vec![0.5, 0.5]));
+        assert_eq!(
+            model.transitions,
+            Some(vec![vec![0.0, 1.0], vec![1.0, 0.0]])
+        );
+    }
+
+    #[test]
+    fn rows_have_to_sum_to_one() {
+        let json = r#"{"symbols": ["a", "b"], "probabilities": [0.5, 0.4]}"#;
+        let err = load("model.json", json).unwrap_err();
+        assert!(err.to_string().contains("sum to"), "{}", err);
+        let toml = "symbols = [\"a\", \"b\"]\ntransitions = [[0.5, 0.5], [0.7, 0.7]]\n";
+        let err = load("model.toml", toml).unwrap_err();
+        assert!(err.to_string().contains("transitions from `b`"), "{}", err);
+        let err = load("model.csv", "from;a;b\n*;1;1\n").unwrap_err();
+        assert!(err.to_string().contains("sum to"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_symbols_are_rejected() {
+        let err = load("model.csv", "from;a;b\na;0;1\nb;1;0\nc;1;0\n").unwrap_err();
+        assert!(err.to_string().contains("`c`"), "{}", err);
+        let json = r#"{"symbols": ["a", "b"], "probabilities": [0.5, 0.5],
+            "contexts": [{"context": ["a", "z"], "probabilities": [0.5, 0.5]}]}"#;
+        let err = load("model.json", json).unwrap_err();
+        assert!(err.to_string().contains("unknown symbol `z`"), "{}", err);
+        let json = r#"{"symbols": ["a", "a"], "probabilities": [0.5, 0.5]}"#;
+        assert!(load("model.json", json).is_err());
+    }
+
+    #[test]
+    fn txt_is_a_matrix_unless_text_is_asked_for() {
+        let matrix = "0.5 0.5\n";
+        let model = load("model.txt", matrix).unwrap();
+        assert_eq!(model.probabilities, Some(vec![0.5, 0.5]));
+        assert_eq!(Format::Auto.resolve(Path::new("model.txt")), Format::Matrix);
+        let trained = Model::load(
+            Path::new("model.txt"),
+            matrix,
+            Format::Text,
+            128,
+            0,
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap();
+        // the digits, point and spaces of the matrix
+        assert_eq!(trained.symbols.len(), 5);
+    }
+}
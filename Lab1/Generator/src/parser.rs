@@ -0,0 +1,316 @@
+use std::{error::Error, fmt, io};
+
+/// Largest deviation of a row sum from 1 that is still accepted
+pub const SUM_TOLERANCE: f64 = 1e-6;
+
+/// What went wrong while reading the probability matrix
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// The file has no numbers at all
+    Empty,
+    /// A field could not be read as a decimal, a fraction or a scientific number
+    InvalidNumber(String),
+    /// A fraction with a zero denominator, e.g. `1/0`
+    ZeroDenominator(String),
+    /// A probability below zero or above one
+    OutOfRange(f64),
+    /// A row with a different number of entries than the first one
+    RowLength {
+        row: usize,
+        found: usize,
+        expected: usize,
+    },
+    /// A transition matrix with a different number of rows and columns
+    NotSquare { rows: usize, columns: usize },
+    /// A row whose probabilities do not add up to 1
+    RowSum { row: usize, sum: f64 },
+}
+
+/// Error returned by [`parse_matrix`], pointing at the line and column
+/// (both starting from 1) where the problem was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::Empty => write!(f, "no probabilities found"),
+            ParseErrorKind::InvalidNumber(field) => write!(f, "`{}` is not a number", field),
+            ParseErrorKind::ZeroDenominator(field) => {
+                write!(f, "`{}` has a zero denominator", field)
+            }
+            ParseErrorKind::OutOfRange(value) => {
+                write!(f, "probability {} is not in range 0..1", value)
+            }
+            ParseErrorKind::RowLength {
+                row,
+                found,
+                expected,
+            } => write!(
+                f,
+                "row {} has {} entries, expected {}",
+                row, found, expected
+            ),
+            ParseErrorKind::NotSquare { rows, columns } => write!(
+                f,
+                "transition matrix has {} rows and {} columns, expected a square matrix",
+                rows, columns
+            ),
+            ParseErrorKind::RowSum { row, sum } => {
+                write!(f, "probabilities in row {} sum to {}, expected 1", row, sum)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(err: ParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// A single number of the matrix together with where it was written
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    value: f64,
+    line: usize,
+    column: usize,
+}
+
+/// Parses the Generator's probability matrix.
+///
+/// Numbers are separated by whitespace or `;` and may be written as
+/// decimals with either `,` or `.` (`0,25`, `.5`), as fractions (`1/3`)
+/// or in scientific notation (`2.5e-1`). Everything after `#` is a comment,
+/// blank lines are skipped.
+///
+/// A single row describes a memoryless source, several rows describe the
+/// transition matrix of a Markov source and have to form a square matrix.
+/// Every row has to sum to 1.
+pub fn parse_matrix(input: &str) -> Result<Vec<Vec<f64>>, ParseError> {
+    let rows = tokenize(input)?;
+    let first = match rows.first() {
+        Some(first) => first,
+        None => {
+            return Err(ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::Empty,
+            })
+        }
+    };
+    let expected = first.len();
+    for (index, row) in rows.iter().enumerate() {
+        if row.len() != expected {
+            return Err(ParseError {
+                line: row[0].line,
+                column: row[0].column,
+                kind: ParseErrorKind::RowLength {
+                    row: index + 1,
+                    found: row.len(),
+                    expected,
+                },
+            });
+        }
+    }
+    if rows.len() != 1 && rows.len() != expected {
+        return Err(ParseError {
+            line: first[0].line,
+            column: first[0].column,
+            kind: ParseErrorKind::NotSquare {
+                rows: rows.len(),
+                columns: expected,
+            },
+        });
+    }
+    for (index, row) in rows.iter().enumerate() {
+        let mut sum = 0.0;
+        for cell in row {
+            if !(0.0..=1.0).contains(&cell.value) {
+                return Err(ParseError {
+                    line: cell.line,
+                    column: cell.column,
+                    kind: ParseErrorKind::OutOfRange(cell.value),
+                });
+            }
+            sum += cell.value;
+        }
+        if (sum - 1.0).abs() > SUM_TOLERANCE {
+            return Err(ParseError {
+                line: row[0].line,
+                column: row[0].column,
+                kind: ParseErrorKind::RowSum {
+                    row: index + 1,
+                    sum,
+                },
+            });
+        }
+    }
+    Ok(rows
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.value).collect())
+        .collect())
+}
+
+/// Splits the input into rows of numbers, skipping blank lines and comments
+fn tokenize(input: &str) -> Result<Vec<Vec<Cell>>, ParseError> {
+    let mut rows = Vec::new();
+    for (line_index, line) in input.lines().enumerate() {
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut field_column = 0;
+        // chain a separator so the last field of the line gets flushed
+        for (column_index, c) in line.chars().chain(std::iter::once(' ')).enumerate() {
+            if c.is_whitespace() || c == ';' || c == '#' {
+                if !field.is_empty() {
+                    let value = parse_number(&field).map_err(|kind| ParseError {
+                        line: line_index + 1,
+                        column: field_column,
+                        kind,
+                    })?;
+                    row.push(Cell {
+                        value,
+                        line: line_index + 1,
+                        column: field_column,
+                    });
+                    field.clear();
+                }
+                if c == '#' {
+                    break;
+                }
+            } else {
+                if field.is_empty() {
+                    field_column = column_index + 1;
+                }
+                field.push(c);
+            }
+        }
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// Reads a decimal, scientific or fractional number
+pub fn parse_number(field: &str) -> Result<f64, ParseErrorKind> {
+    match field.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator = parse_decimal(numerator)
+                .ok_or_else(|| ParseErrorKind::InvalidNumber(field.to_string()))?;
+            let denominator = parse_decimal(denominator)
+                .ok_or_else(|| ParseErrorKind::InvalidNumber(field.to_string()))?;
+            if denominator == 0.0 {
+                return Err(ParseErrorKind::ZeroDenominator(field.to_string()));
+            }
+            Ok(numerator / denominator)
+        }
+        None => {
+            parse_decimal(field).ok_or_else(|| ParseErrorKind::InvalidNumber(field.to_string()))
+        }
+    }
+}
+
+/// Reads a decimal number with `,` or `.` as the separator,
+/// optionally followed by an exponent
+fn parse_decimal(field: &str) -> Option<f64> {
+    // f64::from_str also knows "inf" and "nan", which are no probabilities
+    let valid = field
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '+' | '-' | 'e' | 'E'));
+    if !valid || !field.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    field
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_matrix, parse_number, ParseError, ParseErrorKind};
+
+    #[test]
+    fn numbers_in_every_notation() {
+        assert_eq!(parse_number("0,25"), Ok(0.25));
+        assert_eq!(parse_number("0.25"), Ok(0.25));
+        assert_eq!(parse_number(".5"), Ok(0.5));
+        assert_eq!(parse_number("1/4"), Ok(0.25));
+        assert_eq!(parse_number("2.5e-1"), Ok(0.25));
+        assert_eq!(parse_number("25E-2"), Ok(0.25));
+        assert_eq!(parse_number("1,5/6"), Ok(0.25));
+        assert_eq!(
+            parse_number("1/0"),
+            Err(ParseErrorKind::ZeroDenominator("1/0".to_string()))
+        );
+        for field in ["abc", "inf", "nan", "1/", "/2", "0.5x"] {
+            assert_eq!(
+                parse_number(field),
+                Err(ParseErrorKind::InvalidNumber(field.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn mixed_notations_in_a_matrix() {
+        let matrix =
+            parse_matrix("# transitions\n0,5 1/4 2.5e-1\n\n1 0 0 # stays\n1/3;1/3;1/3\n").unwrap();
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix[0], [0.5, 0.25, 0.25]);
+        assert_eq!(matrix[1], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn row_length_points_at_the_row() {
+        let err = parse_matrix("0.5 0.5\n  1\n").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 2,
+                column: 3,
+                kind: ParseErrorKind::RowLength {
+                    row: 2,
+                    found: 1,
+                    expected: 2,
+                },
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 3: row 2 has 1 entries, expected 2"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_field() {
+        let err = parse_matrix("0.5 0.5\n0.5  1/0\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 6));
+        assert_eq!(err.kind, ParseErrorKind::ZeroDenominator("1/0".to_string()));
+        let err = parse_matrix("0.5 x").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+        assert_eq!(
+            parse_matrix("# nothing\n").unwrap_err().kind,
+            ParseErrorKind::Empty
+        );
+        assert!(matches!(
+            parse_matrix("0.5 0.4").unwrap_err().kind,
+            ParseErrorKind::RowSum { row: 1, .. }
+        ));
+        assert_eq!(
+            parse_matrix("0.5 0.5\n0.5 0.5\n0.5 0.5").unwrap_err().kind,
+            ParseErrorKind::NotSquare {
+                rows: 3,
+                columns: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Walker's alias table, drawing an index `i` with probability
+/// `weights[i] / sum(weights)` in constant time.
+///
+/// Every column `i` keeps the chance `probability[i]` of returning `i` itself,
+/// the rest of the column belongs to `alias[i]`.
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    probability: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds the table with Vose's method.
+    ///
+    /// # Panics
+    /// When `weights` is empty, has a negative entry or sums to zero.
+    pub fn new(weights: &[f64]) -> Self {
+        let n = weights.len();
+        if n == 0 {
+            panic!("provided weights are empty")
+        }
+        if weights.iter().any(|&w| w < 0.0) {
+            panic!("weights cannot be negative")
+        }
+        let sum: f64 = weights.iter().sum();
+        if sum <= 0.0 {
+            panic!("weights have to sum to more than zero")
+        }
+        // scale so that the average column is exactly 1
+        let mut scaled = weights
+            .iter()
+            .map(|w| w * n as f64 / sum)
+            .collect::<Vec<_>>();
+        let mut probability = vec![0.0; n];
+        let mut alias = (0..n).collect::<Vec<_>>();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            probability[s] = scaled[s];
+            alias[s] = l;
+            // the large column fills up the rest of the small one
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // whatever is left is 1 up to rounding errors
+        for i in large.into_iter().chain(small) {
+            probability[i] = 1.0;
+        }
+        Self { probability, alias }
+    }
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let column = rng.gen_range(0..self.probability.len());
+        if rng.gen::<f64>() < self.probability[column] {
+            column
+        } else {
+            self.alias[column]
+        }
+    }
+}
+
+/// First-order Markov source: the first symbol is drawn from the `initial`
+/// distribution, every following one from the transition row of its predecessor
+#[derive(Debug, Clone)]
+pub struct MarkovChain {
+    initial: AliasTable,
+    transitions: Vec<AliasTable>,
+    state: Option<usize>,
+}
+
+impl MarkovChain {
+    /// # Panics
+    /// When `transitions` is not a square matrix with as many rows as `initial`,
+    /// or when any of the distributions is invalid for [`AliasTable::new`].
+    pub fn new(initial: &[f64], transitions: &[Vec<f64>]) -> Self {
+        if transitions.len() != initial.len()
+            || transitions.iter().any(|row| row.len() != initial.len())
+        {
+            panic!("transition matrix does not match the initial distribution")
+        }
+        Self {
+            initial: AliasTable::new(initial),
+            transitions: transitions.iter().map(|row| AliasTable::new(row)).collect(),
+            state: None,
+        }
+    }
+    /// Draws the next symbol and moves the chain into it
+    pub fn sample<R: Rng + ?Sized>(&mut self, rng: &mut R) -> usize {
+        let next = match self.state {
+            Some(state) => self.transitions[state].sample(rng),
+            None => self.initial.sample(rng),
+        };
+        self.state = Some(next);
+        next
+    }
+}
+
+/// Markov source of order k: every symbol is drawn from the distribution
+/// of the k symbols before it.
+///
+/// Contexts without a distribution of their own, as well as the first
+/// k symbols, are drawn from the `fallback` distribution. Without one the
+/// source starts from a randomly chosen listed context.
+#[derive(Debug, Clone)]
+pub struct ContextSource {
+    order: usize,
+    contexts: HashMap<Vec<usize>, AliasTable>,
+    starts: Vec<Vec<usize>>,
+    fallback: Option<AliasTable>,
+    history: Vec<usize>,
+    start: Option<usize>,
+}
+
+impl ContextSource {
+    /// # Panics
+    /// When `contexts` is empty or holds contexts of different lengths,
+    /// or when any of the distributions is invalid for [`AliasTable::new`].
+    pub fn new(contexts: &[(Vec<usize>, &[f64])], fallback: Option<&[f64]>) -> Self {
+        let order = match contexts.first() {
+            Some((context, _)) => context.len(),
+            None => panic!("provided contexts are empty"),
+        };
+        if contexts.iter().any(|(context, _)| context.len() != order) {
+            panic!("contexts have different lengths")
+        }
+        Self {
+            order,
+            contexts: contexts
+                .iter()
+                .map(|(context, probabilities)| (context.clone(), AliasTable::new(probabilities)))
+                .collect(),
+            starts: contexts
+                .iter()
+                .map(|(context, _)| context.clone())
+                .collect(),
+            fallback: fallback.map(AliasTable::new),
+            history: Vec::with_capacity(order),
+            start: None,
+        }
+    }
+    /// The last k drawn symbols, once there are k of them
+    pub fn context(&self) -> Option<&[usize]> {
+        if self.history.len() == self.order {
+            Some(&self.history)
+        } else {
+            None
+        }
+    }
+    /// Draws the next symbol and appends it to the context
+    pub fn sample<R: Rng + ?Sized>(&mut self, rng: &mut R) -> usize {
+        let next = match (self.context(), &self.fallback) {
+            (Some(context), fallback) => match (self.contexts.get(context), fallback) {
+                (Some(table), _) | (None, Some(table)) => table.sample(rng),
+                (None, None) => unreachable!("context {:?} has no distribution", context),
+            },
+            (None, Some(fallback)) => fallback.sample(rng),
+            (None, None) => {
+                let starts = &self.starts;
+                let start = *self
+                    .start
+                    .get_or_insert_with(|| rng.gen_range(0..starts.len()));
+                self.starts[start][self.history.len()]
+            }
+        };
+        if self.history.len() == self.order {
+            self.history.remove(0);
+        }
+        self.history.push(next);
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AliasTable, ContextSource, MarkovChain};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Upper 0.1% point of the chi-squared distribution,
+    /// by the Wilson–Hilferty approximation
+    fn chi_squared_critical(degrees_of_freedom: usize) -> f64 {
+        let k = degrees_of_freedom as f64;
+        let z = 3.090_232;
+        k * (1.0 - 2.0 / (9.0 * k) + z * (2.0 / (9.0 * k)).sqrt()).powi(3)
+    }
+
+    /// Draws `draws` samples and returns the chi-squared statistic
+    /// against the expected frequencies together with its degrees of freedom
+    fn chi_squared(weights: &[f64], draws: usize, seed: u64) -> (f64, usize) {
+        let table = AliasTable::new(weights);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut observed = vec![0usize; weights.len()];
+        for _ in 0..draws {
+            observed[table.sample(&mut rng)] += 1;
+        }
+        chi_squared_of(weights, &observed)
+    }
+
+    /// Chi-squared statistic of the observed counts against the weights
+    fn chi_squared_of(weights: &[f64], observed: &[usize]) -> (f64, usize) {
+        let draws: usize = observed.iter().sum();
+        let sum: f64 = weights.iter().sum();
+        let mut statistic = 0.0;
+        let mut categories = 0;
+        for (&w, &o) in weights.iter().zip(observed) {
+            let expected = w / sum * draws as f64;
+            if expected == 0.0 {
+                assert_eq!(o, 0, "drew a symbol with zero probability");
+                continue;
+            }
+            statistic += (o as f64 - expected).powi(2) / expected;
+            categories += 1;
+        }
+        (statistic, categories - 1)
+    }
+
+    #[test]
+    fn uniform_frequencies_match() {
+        let (statistic, df) = chi_squared(&[0.25; 4], 100_000, 1);
+        assert!(statistic < chi_squared_critical(df), "chi2 = {}", statistic);
+    }
+
+    #[test]
+    fn skewed_frequencies_match() {
+        let weights = [0.5, 0.25, 0.125, 0.0625, 0.03125, 0.03125];
+        let (statistic, df) = chi_squared(&weights, 200_000, 2);
+        assert!(statistic < chi_squared_critical(df), "chi2 = {}", statistic);
+    }
+
+    #[test]
+    fn unequal_unsorted_frequencies_match() {
+        let weights = [0.1, 0.6, 0.05, 0.2, 0.05];
+        let (statistic, df) = chi_squared(&weights, 200_000, 3);
+        assert!(statistic < chi_squared_critical(df), "chi2 = {}", statistic);
+    }
+
+    #[test]
+    fn zero_weights_are_never_drawn() {
+        let (statistic, df) = chi_squared(&[0.0, 0.7, 0.0, 0.3], 50_000, 4);
+        assert!(statistic < chi_squared_critical(df), "chi2 = {}", statistic);
+    }
+
+    #[test]
+    fn single_symbol_is_always_drawn() {
+        let table = AliasTable::new(&[1.0]);
+        let mut rng = StdRng::seed_from_u64(5);
+        assert!((0..1000).all(|_| table.sample(&mut rng) == 0));
+    }
+
+    #[test]
+    fn markov_transitions_match_rows() {
+        let transitions = vec![
+            vec![0.9, 0.1, 0.0],
+            vec![0.2, 0.5, 0.3],
+            vec![0.0, 0.6, 0.4],
+        ];
+        let mut chain = MarkovChain::new(&[1.0 / 3.0; 3], &transitions);
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut observed = vec![vec![0usize; 3]; 3];
+        let mut prev = chain.sample(&mut rng);
+        for _ in 0..300_000 {
+            let next = chain.sample(&mut rng);
+            observed[prev][next] += 1;
+            prev = next;
+        }
+        for (row, counts) in transitions.iter().zip(&observed) {
+            let (statistic, df) = chi_squared_of(row, counts);
+            assert!(statistic < chi_squared_critical(df), "chi2 = {}", statistic);
+        }
+    }
+
+    #[test]
+    fn context_source_follows_its_contexts() {
+        // the next bit is the xor of the two before it
+        let zero: &[f64] = &[1.0, 0.0];
+        let one: &[f64] = &[0.0, 1.0];
+        let contexts = vec![
+            (vec![0, 0], zero),
+            (vec![0, 1], one),
+            (vec![1, 0], one),
+            (vec![1, 1], zero),
+        ];
+        let mut source = ContextSource::new(&contexts, None);
+        let mut rng = StdRng::seed_from_u64(7);
+        let bits = (0..100)
+            .map(|_| source.sample(&mut rng))
+            .collect::<Vec<_>>();
+        assert!(bits.windows(3).all(|w| w[2] == w[0] ^ w[1]));
+    }
+}
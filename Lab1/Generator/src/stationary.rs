@@ -0,0 +1,391 @@
+use crate::parser::SUM_TOLERANCE;
+use std::{error::Error, fmt, io};
+
+/// Power iteration stops once a step moves the distribution less than this
+const CONVERGENCE: f64 = 1e-15;
+/// Rough bound on the multiplications spent in power iteration before
+/// falling back to solving the linear system directly
+const ITERATION_BUDGET: usize = 100_000_000;
+
+/// Why a transition matrix has no unique stationary distribution
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainError {
+    /// The matrix has no rows
+    Empty,
+    /// A transition matrix with a different number of rows and columns
+    NotSquare { rows: usize, columns: usize },
+    /// A transition probability outside 0..1
+    InvalidProbability {
+        row: usize,
+        column: usize,
+        value: f64,
+    },
+    /// A row whose probabilities do not add up to 1
+    RowSum { row: usize, sum: f64 },
+    /// The chain falls apart into several closed classes of states and ends up
+    /// in one or another depending on where it starts. Rows start from 1.
+    NotUnique { classes: Vec<Vec<usize>> },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Empty => write!(f, "transition matrix is empty"),
+            ChainError::NotSquare { rows, columns } => write!(
+                f,
+                "transition matrix has {} rows and {} columns, expected a square matrix",
+                rows, columns
+            ),
+            ChainError::InvalidProbability { row, column, value } => write!(
+                f,
+                "transition probability {} in row {}, column {} is not in range 0..1",
+                value, row, column
+            ),
+            ChainError::RowSum { row, sum } => {
+                write!(f, "probabilities in row {} sum to {}, expected 1", row, sum)
+            }
+            ChainError::NotUnique { classes } => write!(
+                f,
+                "chain is reducible into {} closed classes of rows {:?}, \
+                 it has no unique stationary distribution",
+                classes.len(),
+                classes
+            ),
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+impl From<ChainError> for io::Error {
+    fn from(err: ChainError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// The long-run behaviour of a Markov chain with a unique stationary distribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stationary {
+    /// Stationary probability of every state, 0 for transient ones
+    pub distribution: Vec<f64>,
+    /// Period of the recurrent states, 1 for an aperiodic chain. A periodic
+    /// chain still has a unique stationary distribution, but the probability
+    /// of being in a state keeps cycling instead of converging to it.
+    pub period: usize,
+    /// States the chain eventually leaves for good, empty for an irreducible chain
+    pub transient: Vec<usize>,
+}
+
+impl Stationary {
+    pub fn is_irreducible(&self) -> bool {
+        self.transient.is_empty()
+    }
+    pub fn is_aperiodic(&self) -> bool {
+        self.period == 1
+    }
+}
+
+/// Finds the stationary distribution π = πP of the transition matrix.
+///
+/// The matrix has to be stochastic and have exactly one closed class of
+/// states. The distribution is found by power iteration on the lazy chain
+/// (P + I) / 2, which shares π with P but converges even for periodic chains;
+/// should that take too long the linear system is solved directly.
+pub fn stationary(transitions: &[Vec<f64>]) -> Result<Stationary, ChainError> {
+    validate(transitions)?;
+    let n = transitions.len();
+    let classes = communicating_classes(transitions);
+    let mut closed = classes
+        .iter()
+        .filter(|class| {
+            class.iter().all(|&i| {
+                (0..n).all(|j| transitions[i][j] == 0.0 || class.binary_search(&j).is_ok())
+            })
+        })
+        .collect::<Vec<_>>();
+    closed.sort();
+    if closed.len() != 1 {
+        return Err(ChainError::NotUnique {
+            classes: closed
+                .iter()
+                .map(|class| class.iter().map(|i| i + 1).collect())
+                .collect(),
+        });
+    }
+    let recurrent = closed[0];
+    let transient = (0..n)
+        .filter(|i| recurrent.binary_search(i).is_err())
+        .collect::<Vec<_>>();
+
+    // transient states end up with probability 0, only the closed class matters
+    let restricted = recurrent
+        .iter()
+        .map(|&i| recurrent.iter().map(|&j| transitions[i][j]).collect())
+        .collect::<Vec<Vec<f64>>>();
+    let solution = power_iteration(&restricted).unwrap_or_else(|| solve(&restricted));
+    let mut distribution = vec![0.0; n];
+    for (&state, p) in recurrent.iter().zip(solution) {
+        distribution[state] = p;
+    }
+    Ok(Stationary {
+        distribution,
+        period: period(&restricted),
+        transient,
+    })
+}
+
+fn validate(transitions: &[Vec<f64>]) -> Result<(), ChainError> {
+    let n = transitions.len();
+    if n == 0 {
+        return Err(ChainError::Empty);
+    }
+    for (i, row) in transitions.iter().enumerate() {
+        if row.len() != n {
+            return Err(ChainError::NotSquare {
+                rows: n,
+                columns: row.len(),
+            });
+        }
+        if let Some((j, &value)) = row
+            .iter()
+            .enumerate()
+            .find(|(_, p)| !(0.0..=1.0).contains(*p))
+        {
+            return Err(ChainError::InvalidProbability {
+                row: i + 1,
+                column: j + 1,
+                value,
+            });
+        }
+        let sum: f64 = row.iter().sum();
+        if (sum - 1.0).abs() > SUM_TOLERANCE {
+            return Err(ChainError::RowSum { row: i + 1, sum });
+        }
+    }
+    Ok(())
+}
+
+/// Strongly connected components of the transition graph by Kosaraju's
+/// algorithm, every one sorted
+fn communicating_classes(transitions: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = transitions.len();
+    // first pass: order the states by the time their depth-first search finishes
+    let mut visited = vec![false; n];
+    let mut finished = Vec::with_capacity(n);
+    for start in 0..n {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some((state, next)) = stack.pop() {
+            match (next..n).find(|&j| transitions[state][j] > 0.0 && !visited[j]) {
+                Some(j) => {
+                    stack.push((state, j + 1));
+                    visited[j] = true;
+                    stack.push((j, 0));
+                }
+                None => finished.push(state),
+            }
+        }
+    }
+    // second pass: search the reversed graph in reverse finishing order
+    let mut class_of = vec![usize::MAX; n];
+    let mut classes = Vec::new();
+    for &start in finished.iter().rev() {
+        if class_of[start] != usize::MAX {
+            continue;
+        }
+        let mut class = vec![start];
+        class_of[start] = classes.len();
+        let mut stack = vec![start];
+        while let Some(state) = stack.pop() {
+            for i in 0..n {
+                if transitions[i][state] > 0.0 && class_of[i] == usize::MAX {
+                    class_of[i] = classes.len();
+                    class.push(i);
+                    stack.push(i);
+                }
+            }
+        }
+        class.sort_unstable();
+        classes.push(class);
+    }
+    classes
+}
+
+/// Period of an irreducible chain: the gcd of `level(u) + 1 - level(v)` over
+/// all transitions u -> v, where levels are breadth-first distances from state 0
+fn period(transitions: &[Vec<f64>]) -> usize {
+    let n = transitions.len();
+    let mut level = vec![usize::MAX; n];
+    level[0] = 0;
+    let mut queue = std::collections::VecDeque::from(vec![0]);
+    let mut period = 0;
+    while let Some(u) = queue.pop_front() {
+        for v in (0..n).filter(|&v| transitions[u][v] > 0.0) {
+            if level[v] == usize::MAX {
+                level[v] = level[u] + 1;
+                queue.push_back(v);
+            } else {
+                period = gcd(period, (level[u] + 1).abs_diff(level[v]));
+            }
+        }
+    }
+    period.max(1)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Iterates π ← π(P + I) / 2 from the uniform distribution,
+/// `None` when it has not converged within the budget
+fn power_iteration(transitions: &[Vec<f64>]) -> Option<Vec<f64>> {
+    let n = transitions.len();
+    let mut pi = vec![1.0 / n as f64; n];
+    for _ in 0..(ITERATION_BUDGET / (n * n)).max(100) {
+        let mut next = pi.iter().map(|p| p / 2.0).collect::<Vec<_>>();
+        for (row, &p) in transitions.iter().zip(&pi) {
+            for (target, &q) in next.iter_mut().zip(row) {
+                *target += p * q / 2.0;
+            }
+        }
+        let change: f64 = next.iter().zip(&pi).map(|(a, b)| (a - b).abs()).sum();
+        pi = next;
+        if change < CONVERGENCE {
+            return Some(pi);
+        }
+    }
+    None
+}
+
+/// Solves π(P - I) = 0 together with Σπ = 1 by Gaussian elimination with
+/// partial pivoting, the last balance equation being replaced by the sum.
+/// Round-off can leave tiny negative components, which are clamped to zero
+fn solve(transitions: &[Vec<f64>]) -> Vec<f64> {
+    let n = transitions.len();
+    // row i of the system is the balance of state i: Σ_j π_j (P_ji - δ_ji) = 0
+    let mut system = (0..n)
+        .map(|i| {
+            let mut row = (0..n)
+                .map(|j| transitions[j][i] - if i == j { 1.0 } else { 0.0 })
+                .collect::<Vec<_>>();
+            row.push(0.0);
+            row
+        })
+        .collect::<Vec<_>>();
+    system[n - 1] = vec![1.0; n + 1];
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs()))
+            .unwrap();
+        system.swap(column, pivot);
+        let pivot_row = system[column].clone();
+        for row in system.iter_mut().skip(column + 1) {
+            let factor = row[column] / pivot_row[column];
+            for (value, &pivot_value) in row.iter_mut().zip(&pivot_row).skip(column) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    let mut pi = vec![0.0; n];
+    for i in (0..n).rev() {
+        let known: f64 = (i + 1..n).map(|j| system[i][j] * pi[j]).sum();
+        pi[i] = (system[i][n] - known) / system[i][i];
+    }
+    clamp_to_distribution(pi)
+}
+
+/// Clamps negative components to zero and rescales the rest to sum to 1
+fn clamp_to_distribution(mut pi: Vec<f64>) -> Vec<f64> {
+    pi.iter_mut().for_each(|p| *p = p.max(0.0));
+    let sum: f64 = pi.iter().sum();
+    if sum > 0.0 {
+        pi.iter_mut().for_each(|p| *p /= sum);
+    }
+    pi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clamp_to_distribution, solve, stationary, ChainError};
+
+    fn close(a: &[f64], b: &[f64]) -> bool {
+        a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9)
+    }
+
+    #[test]
+    fn two_state_chain() {
+        let chain = stationary(&[vec![0.9, 0.1], vec![0.5, 0.5]]).unwrap();
+        assert!(close(&chain.distribution, &[5.0 / 6.0, 1.0 / 6.0]));
+        assert!(chain.is_irreducible() && chain.is_aperiodic());
+    }
+
+    #[test]
+    fn direct_solve_matches() {
+        let transitions = vec![
+            vec![0.9, 0.1, 0.0],
+            vec![0.2, 0.5, 0.3],
+            vec![0.0, 0.6, 0.4],
+        ];
+        let chain = stationary(&transitions).unwrap();
+        assert!(close(&chain.distribution, &solve(&transitions)));
+    }
+
+    #[test]
+    fn round_off_is_clamped() {
+        let pi = clamp_to_distribution(vec![0.5, -1e-17, 0.5 + 1e-12]);
+        assert!(pi.iter().all(|&p| p >= 0.0));
+        assert!((pi.iter().sum::<f64>() - 1.0).abs() < 1e-15);
+        // the transient third state has no stationary mass
+        let pi = solve(&[
+            vec![0.5, 0.5, 0.0],
+            vec![0.3, 0.7, 0.0],
+            vec![0.1, 0.2, 0.7],
+        ]);
+        assert!(pi.iter().all(|&p| p >= 0.0), "{:?}", pi);
+        assert!(close(&pi, &[0.375, 0.625, 0.0]));
+        crate::sampler::AliasTable::new(&pi);
+    }
+
+    #[test]
+    fn periodic_chain_is_solved() {
+        let chain = stationary(&[
+            vec![0.0, 1.0, 0.0],
+            vec![0.0, 0.0, 1.0],
+            vec![1.0, 0.0, 0.0],
+        ])
+        .unwrap();
+        assert!(close(&chain.distribution, &[1.0 / 3.0; 3]));
+        assert_eq!(chain.period, 3);
+    }
+
+    #[test]
+    fn transient_states_vanish() {
+        let chain = stationary(&[vec![0.5, 0.5], vec![0.0, 1.0]]).unwrap();
+        assert!(close(&chain.distribution, &[0.0, 1.0]));
+        assert_eq!(chain.transient, vec![0]);
+    }
+
+    #[test]
+    fn several_closed_classes_are_rejected() {
+        let err = stationary(&[
+            vec![1.0, 0.0, 0.0],
+            vec![0.3, 0.4, 0.3],
+            vec![0.0, 0.0, 1.0],
+        ])
+        .unwrap_err();
+        assert!(matches!(err, ChainError::NotUnique { classes } if classes.len() == 2));
+    }
+
+    #[test]
+    fn non_stochastic_rows_are_rejected() {
+        let err = stationary(&[vec![0.5, 0.4], vec![0.5, 0.5]]).unwrap_err();
+        assert!(matches!(err, ChainError::RowSum { row: 1, .. }));
+    }
+}
@@ -1,44 +1,213 @@
+use clap::Parser;
+use generator::model::{Format, Model};
+use ngram::Sample;
 use std::fs::File;
-use std::io::{Read, Seek};
-use std::{collections::HashMap, env};
-fn help() {
-    println!("check_for_generator.exe In.txt");
+use std::io::{prelude::*, BufWriter};
+use std::path::Path;
+use table::{OutputFormat, Row, SortBy};
+use trainer::Smoothing;
+mod ngram;
+mod table;
+mod trainer;
+mod verify;
+#[derive(Parser)]
+#[clap(version = "1.0", author = "Aleksey S. <siroggi5@gmail.com>")]
+enum Opts {
+    /// Counts the n-grams of a text and estimates its entropy
+    Stats(Stats),
+    /// Trains a first-order model for the Generator from a text
+    Train(Train),
+    /// Checks text produced by the Generator against its model
+    Verify(Verify),
+}
+#[derive(Parser)]
+struct Stats {
+    /// Sets the name of the text file
+    #[clap(short, long)]
+    input: String,
+    /// Sets the name of the file the table is written to, by default it is printed
+    #[clap(short, long)]
+    output: Option<String>,
+    /// Sets the length of the counted n-grams
+    #[clap(short = 'n', long, validator(greater_than_zero), default_value = "1")]
+    order: usize,
+    /// Sets the order of the rows
+    #[clap(short, long, arg_enum, default_value = "count")]
+    sort: SortBy,
+    /// Keeps only the first K rows
+    #[clap(short, long)]
+    top: Option<usize>,
+    /// Sets the format of the table
+    #[clap(short, long, arg_enum, default_value = "table")]
+    format: OutputFormat,
+    /// Sets up to which n the entropies H_n are estimated (only printed with `--format table`)
+    #[clap(short, long, default_value = "4")]
+    entropy: usize,
+}
+#[derive(Parser)]
+struct Train {
+    /// Sets the name of the sample text
+    #[clap(short, long)]
+    input: String,
+    /// Sets the name of the model, its format is chosen by the extension
+    #[clap(short, long)]
+    output: String,
+    /// Sets how transitions that never occurred in the sample are estimated
+    #[clap(short, long, possible_values(&["none", "laplace", "good-turing"]), default_value = "none")]
+    smoothing: Smoothing,
+}
+#[derive(Parser)]
+struct Verify {
+    /// Sets the name of the model the Generator was given
+    #[clap(short, long)]
+    model: String,
+    /// Sets the name of the text the Generator produced
+    #[clap(short, long)]
+    input: String,
+    /// Sets the significance level of all tests together
+    #[clap(short, long, validator(probability), default_value = "0.01")]
+    alpha: f64,
+}
+fn greater_than_zero(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(String::from("order needed to be greater than zero")),
+    }
+}
+fn probability(val: &str) -> Result<(), String> {
+    match val.parse::<f64>() {
+        Ok(alpha) if alpha > 0.0 && alpha < 1.0 => Ok(()),
+        _ => Err(String::from("Not in range 0..1")),
+    }
 }
 fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        2 => {
-            let mut file_in = File::open(&args[1])?;
-            let mut in_matrix = String::new();
-            file_in.read_to_string(&mut in_matrix)?;
-            let len = in_matrix.chars().count();
-            let vc = in_matrix.chars().collect::<Vec<_>>();
-            let mut basic = build_weights(vc);
-            println!("{:#?}", basic);
+    match Opts::parse() {
+        Opts::Stats(opts) => run_stats(&opts),
+        Opts::Train(opts) => {
+            let model = trainer::train(&Sample::new(&read_chars(&opts.input)?), opts.smoothing)?;
+            let path = Path::new(&opts.output);
+            let text = model.serialize(Format::Auto.resolve(path))?;
+            File::create(path)?.write_all(text.as_bytes())?;
+            println!(
+                "Trained a model of {} symbols with {:?} smoothing into {}",
+                model.symbols.len(),
+                opts.smoothing,
+                path.display()
+            );
             Ok(())
         }
-        _ => {
-            println!("Provided {} args", args.len() - 1);
-            help();
+        Opts::Verify(opts) => {
+            if !run_verify(Path::new(&opts.model), &opts.input, opts.alpha)? {
+                std::process::exit(1);
+            }
             Ok(())
         }
     }
 }
-pub fn build_weights(s: Vec<char>) -> HashMap<String, usize> {
-    let mut h = HashMap::new();
-    for i in 0..s.len() {
-        let counter = h.entry(format!("{}", s[i])).or_insert(0);
-        *counter += 1;
-        if i != 0 {
-            let counter = h.entry(format!("{}|{}", s[i - 1], s[i])).or_insert(0);
-            *counter += 1;
+/// Writes the n-gram frequency table of the sample, followed by
+/// its entropy estimates when it is printed as a table
+fn run_stats(opts: &Stats) -> std::io::Result<()> {
+    let sample = Sample::new(&read_chars(&opts.input)?);
+    let counts = sample.count(opts.order);
+    let total: usize = counts.values().sum();
+    let mut rows = counts
+        .into_iter()
+        .map(|(ngram, count)| Row {
+            ngram: sample.text(ngram),
+            count,
+            probability: count as f64 / total as f64,
+        })
+        .collect::<Vec<_>>();
+    table::arrange(&mut rows, opts.sort, opts.top);
+    let mut out: Box<dyn Write> = match &opts.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    table::write(&rows, opts.format, &mut out)?;
+    if opts.format == OutputFormat::Table && opts.entropy > 0 {
+        write_entropies(&sample, opts.entropy, &mut out)?;
+    }
+    out.flush()
+}
+/// Writes the entropy estimates of the sample for n = 1..=k
+fn write_entropies<W: Write>(sample: &Sample, k: usize, out: &mut W) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "{} symbols, alphabet of {}",
+        sample.ids.len(),
+        sample.symbols.len()
+    )?;
+    writeln!(
+        out,
+        "{:>2} {:>10} {:>10} {:>10} {:>14}",
+        "n", "n-grams", "H_n", "H_n/n", "H(X_n|X^n-1)"
+    )?;
+    for estimate in sample.entropies(k) {
+        writeln!(
+            out,
+            "{:>2} {:>10} {:>10.4} {:>10.4} {:>14.4}",
+            estimate.n,
+            estimate.distinct,
+            estimate.block,
+            estimate.block / estimate.n as f64,
+            estimate.conditional
+        )?;
+    }
+    Ok(())
+}
+/// Checks the generated text against the model state by state and prints
+/// a table of the tests, returns whether the output follows the model
+fn run_verify(model_path: &Path, text_path: &str, alpha: f64) -> std::io::Result<bool> {
+    let model = Model::read(model_path)?;
+    let text = read_chars(text_path)?.into_iter().collect::<String>();
+    let tests = verify::verify(&model, &model.tokenize(&text)?);
+    let tested = tests.iter().filter(|test| test.is_tested()).count();
+    // Bonferroni correction, every state is a test of its own
+    let corrected = alpha / tested.max(1) as f64;
+    println!(
+        "{:<12} {:>8} {:>4} {:>12} {:>10} {:>12} {:>10}  verdict",
+        "state", "n", "df", "chi2", "p", "G", "p"
+    );
+    let mut rejected = 0;
+    for test in &tests {
+        let verdict = if test.impossible {
+            "impossible symbol"
+        } else if !test.is_tested() {
+            "skipped, too few samples"
+        } else if test.rejected(corrected) {
+            "rejected"
+        } else {
+            "ok"
+        };
+        if test.rejected(corrected) {
+            rejected += 1;
         }
+        let state = if test.state.is_empty() {
+            String::from("*")
+        } else {
+            format!("{:?}", test.state.concat())
+        };
+        println!(
+            "{:<12} {:>8} {:>4} {:>12.4} {:>10.4e} {:>12.4} {:>10.4e}  {}",
+            state,
+            test.observations,
+            test.degrees_of_freedom,
+            test.chi_squared,
+            test.chi_squared_p,
+            test.g,
+            test.g_p,
+            verdict
+        );
     }
-    h
+    println!(
+        "{} of {} tested states deviate from the model at alpha={} (per state {:e})",
+        rejected, tested, alpha, corrected
+    );
+    Ok(rejected == 0)
 }
-pub fn build_second(s: &str) -> HashMap<String, usize> {
-    let mut h = HashMap::new();
-    for (index, ch) in s.char_indices() {}
-
-    h
+fn read_chars(path: &str) -> std::io::Result<Vec<char>> {
+    let mut file_in = File::open(path)?;
+    let mut in_matrix = String::new();
+    file_in.read_to_string(&mut in_matrix)?;
+    Ok(in_matrix.chars().collect::<Vec<_>>())
 }
@@ -0,0 +1,121 @@
+use compression::information;
+use std::collections::{BTreeSet, HashMap};
+
+/// Sample text with every character replaced by its index in the sorted alphabet
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub symbols: Vec<char>,
+    pub ids: Vec<usize>,
+}
+
+/// Empirical entropies of the n-grams of one length
+#[derive(Debug, Clone, Copy)]
+pub struct Entropy {
+    pub n: usize,
+    /// Number of distinct n-grams in the sample
+    pub distinct: usize,
+    /// Block entropy H_n of the n-grams, in bits
+    pub block: f64,
+    /// Conditional entropy H(X_n | X_1..X_n-1) = H_n - H_n-1, in bits per symbol
+    pub conditional: f64,
+}
+
+impl Sample {
+    pub fn new(chars: &[char]) -> Self {
+        let symbols = chars
+            .iter()
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let index = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (c, i))
+            .collect::<HashMap<_, _>>();
+        Self {
+            ids: chars.iter().map(|c| index[c]).collect(),
+            symbols,
+        }
+    }
+    /// Counts every n-gram of the sample, overlapping ones included
+    pub fn count(&self, n: usize) -> HashMap<&[usize], usize> {
+        let mut counts = HashMap::new();
+        if n == 0 {
+            return counts;
+        }
+        for window in self.ids.windows(n) {
+            *counts.entry(window).or_insert(0) += 1;
+        }
+        counts
+    }
+    /// Writes an n-gram of symbol ids back as text
+    pub fn text(&self, ngram: &[usize]) -> String {
+        ngram.iter().map(|&id| self.symbols[id]).collect()
+    }
+    /// Entropy estimates for n = 1..=k. Once the conditional entropy stops
+    /// falling with n the source has no memory longer than n - 1 symbols,
+    /// as long as the sample is long enough to hold most of the n-grams.
+    pub fn entropies(&self, k: usize) -> Vec<Entropy> {
+        let mut previous = 0.0;
+        (1..=k)
+            .map(|n| {
+                let counts = self.count(n);
+                let block = information::entropy_of_counts(counts.values().copied());
+                // H_n never decreases, anything below zero is rounding
+                let conditional = (block - previous).max(0.0);
+                previous = block;
+                Entropy {
+                    n,
+                    distinct: counts.len(),
+                    block,
+                    conditional,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sample;
+
+    fn sample(text: &str) -> Sample {
+        Sample::new(&text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn constant_text_has_no_entropy() {
+        for estimate in sample(&"a".repeat(100)).entropies(4) {
+            assert_eq!(estimate.distinct, 1);
+            assert_eq!(estimate.block, 0.0);
+            assert_eq!(estimate.conditional, 0.0);
+        }
+    }
+
+    #[test]
+    fn alternating_text_is_one_bit_without_memory() {
+        let estimates = sample(&"ab".repeat(500)).entropies(3);
+        assert!((estimates[0].block - 1.0).abs() < 1e-12);
+        assert!((estimates[0].conditional - 1.0).abs() < 1e-12);
+        // "ab" and "ba" are about as frequent, the next symbol is then known
+        assert_eq!(estimates[1].distinct, 2);
+        assert!((estimates[1].block - 1.0).abs() < 1e-5);
+        assert!(estimates[1].conditional < 1e-5);
+        assert!(estimates[2].conditional < 1e-5);
+    }
+
+    #[test]
+    fn n_past_the_sample_finds_no_ngrams() {
+        let sample = sample("abc");
+        assert_eq!(sample.count(4).len(), 0);
+        let estimates = sample.entropies(5);
+        assert_eq!(estimates.len(), 5);
+        assert_eq!(estimates[2].distinct, 1);
+        for estimate in &estimates[3..] {
+            assert_eq!(estimate.distinct, 0);
+            assert_eq!(estimate.block, 0.0);
+            assert_eq!(estimate.conditional, 0.0);
+        }
+    }
+}
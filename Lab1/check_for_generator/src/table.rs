@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How the frequency table is written
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns for reading in the terminal
+    Table,
+    /// `;`-separated values with a header, for spreadsheets
+    Csv,
+    /// An array of `{"ngram", "count", "probability"}` objects,
+    /// the weight table the Coder reads
+    Json,
+}
+
+/// Order of the rows of the frequency table
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Most frequent first, ties by n-gram
+    Count,
+    /// By n-gram
+    Ngram,
+}
+
+/// One n-gram of the frequency table
+#[derive(Debug, Clone, Serialize)]
+pub struct Row {
+    pub ngram: String,
+    pub count: usize,
+    pub probability: f64,
+}
+
+/// Sorts the rows and keeps the first `top` of them, all of them for `None`
+pub fn arrange(rows: &mut Vec<Row>, sort: SortBy, top: Option<usize>) {
+    match sort {
+        SortBy::Count => rows.sort_by(|a, b| b.count.cmp(&a.count).then(a.ngram.cmp(&b.ngram))),
+        SortBy::Ngram => rows.sort_by(|a, b| a.ngram.cmp(&b.ngram)),
+    }
+    if let Some(top) = top {
+        rows.truncate(top);
+    }
+}
+
+pub fn write<W: Write>(rows: &[Row], format: OutputFormat, out: &mut W) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            writeln!(
+                out,
+                "{:<12} {:>10} {:>12}",
+                "n-gram", "count", "probability"
+            )?;
+            for row in rows {
+                writeln!(
+                    out,
+                    "{:<12} {:>10} {:>12.6}",
+                    format!("{:?}", row.ngram),
+                    row.count,
+                    row.probability
+                )?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(out);
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// The rows the Coder reads with `--weights`, which ignores the probability
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct WeightRow {
+        ngram: String,
+        count: usize,
+    }
+
+    fn rows() -> Vec<Row> {
+        [("b", 2), ("a", 2), ("ą", 5), ("c", 1)]
+            .iter()
+            .map(|&(ngram, count)| Row {
+                ngram: ngram.to_string(),
+                count,
+                probability: count as f64 / 10.0,
+            })
+            .collect()
+    }
+
+    fn ngrams(rows: &[Row]) -> Vec<&str> {
+        rows.iter().map(|row| row.ngram.as_str()).collect()
+    }
+
+    fn written(rows: &[Row], format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write(rows, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sorted_by_count_then_ngram() {
+        let mut rows = rows();
+        arrange(&mut rows, SortBy::Count, None);
+        assert_eq!(ngrams(&rows), ["ą", "a", "b", "c"]);
+        arrange(&mut rows, SortBy::Ngram, None);
+        assert_eq!(ngrams(&rows), ["a", "b", "c", "ą"]);
+    }
+
+    #[test]
+    fn top_keeps_the_first_rows() {
+        let mut top = rows();
+        arrange(&mut top, SortBy::Count, Some(2));
+        assert_eq!(ngrams(&top), ["ą", "a"]);
+        let mut all = rows();
+        arrange(&mut all, SortBy::Ngram, Some(10));
+        assert_eq!(all.len(), 4);
+        arrange(&mut all, SortBy::Ngram, Some(0));
+        assert!(all.is_empty());
+    }
+
+    #[test]
+    fn csv_and_json_output() {
+        let rows = &rows()[..2];
+        assert_eq!(
+            written(rows, OutputFormat::Csv),
+            "ngram;count;probability\nb;2;0.2\na;2;0.2\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&written(rows, OutputFormat::Json)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"ngram": "b", "count": 2, "probability": 0.2},
+                {"ngram": "a", "count": 2, "probability": 0.2}
+            ])
+        );
+        let table = written(rows, OutputFormat::Table);
+        assert_eq!(table.lines().count(), 3);
+        assert!(table.lines().nth(1).unwrap().starts_with("\"b\""));
+    }
+
+    #[test]
+    fn reads_back_as_coder_weights() {
+        let rows = rows();
+        let expected = rows
+            .iter()
+            .map(|row| WeightRow {
+                ngram: row.ngram.clone(),
+                count: row.count,
+            })
+            .collect::<Vec<_>>();
+        let json: Vec<WeightRow> =
+            serde_json::from_str(&written(&rows, OutputFormat::Json)).unwrap();
+        assert_eq!(json, expected);
+        let csv = written(&rows, OutputFormat::Csv);
+        let csv: Vec<WeightRow> = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(csv, expected);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::ngram::Sample;
+use generator::model::{normalize, Model};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// How probabilities of transitions that never occurred in the sample are estimated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Smoothing {
+    /// Plain relative frequencies, unseen transitions get probability 0
+    None,
+    /// Add-one smoothing, every transition counts as seen once more
+    Laplace,
+    /// Simple Good–Turing, unseen transitions share the mass of the ones seen once
+    GoodTuring,
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Smoothing::None),
+            "laplace" => Ok(Smoothing::Laplace),
+            "good-turing" => Ok(Smoothing::GoodTuring),
+            _ => Err(format!(
+                "unknown smoothing `{}`, expected none, laplace or good-turing",
+                s
+            )),
+        }
+    }
+}
+
+/// Builds a first-order Markov model out of the unigram and bigram
+/// counts of the sample.
+///
+/// The unigram frequencies become the memoryless `probabilities`, the bigram
+/// counts are normalised row by row into the `transitions` matrix. A symbol
+/// that is never followed by anything (the last one of the sample) gets the
+/// unigram frequencies as its row. An empty sample has no frequencies to
+/// build a model from and is rejected.
+pub fn train(sample: &Sample, smoothing: Smoothing) -> Result<Model, Error> {
+    if sample.ids.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "sample is empty, there is nothing to train on".to_string(),
+        ));
+    }
+    let n = sample.symbols.len();
+    let mut unigrams = vec![0; n];
+    for (ngram, count) in sample.count(1) {
+        unigrams[ngram[0]] = count;
+    }
+    let mut rows = vec![vec![0; n]; n];
+    for (ngram, count) in sample.count(2) {
+        rows[ngram[0]][ngram[1]] = count;
+    }
+    let probabilities = normalize(&unigrams);
+    let transitions = rows
+        .iter()
+        .map(|row| {
+            if row.iter().all(|&count| count == 0) && smoothing != Smoothing::Laplace {
+                probabilities.clone()
+            } else {
+                smooth(row, smoothing)
+            }
+        })
+        .collect();
+    Ok(Model {
+        symbols: sample.symbols.iter().map(char::to_string).collect(),
+        probabilities: Some(probabilities),
+        transitions: Some(transitions),
+        contexts: None,
+    })
+}
+
+/// Turns the counts of one row into probabilities
+fn smooth(row: &[usize], smoothing: Smoothing) -> Vec<f64> {
+    match smoothing {
+        Smoothing::None => normalize(row),
+        Smoothing::Laplace => normalize(&row.iter().map(|count| count + 1).collect::<Vec<_>>()),
+        Smoothing::GoodTuring => good_turing(row),
+    }
+}
+
+/// Simple Good–Turing estimate: a count `r` is adjusted to
+/// `(r + 1) * N(r + 1) / N(r)`, where `N(r)` is the number of symbols seen
+/// exactly `r` times, and the unseen symbols evenly share `N(1) / N`.
+/// Counts whose `N(r + 1)` is zero are kept as they are. A row of nothing but
+/// single occurrences leaves nothing to estimate from and falls back to Laplace.
+fn good_turing(row: &[usize]) -> Vec<f64> {
+    let total: usize = row.iter().sum();
+    let mut frequencies = HashMap::new();
+    for &count in row.iter().filter(|&&count| count > 0) {
+        *frequencies.entry(count).or_insert(0usize) += 1;
+    }
+    let singles = frequencies.get(&1).copied().unwrap_or(0);
+    if singles == total {
+        return smooth(row, Smoothing::Laplace);
+    }
+    let unseen = row.iter().filter(|&&count| count == 0).count();
+    let unseen_mass = if unseen > 0 {
+        singles as f64 / total as f64
+    } else {
+        0.0
+    };
+    let adjusted = row
+        .iter()
+        .map(
+            |&count| match (frequencies.get(&count), frequencies.get(&(count + 1))) {
+                (Some(&n), Some(&next)) if count > 0 => (count + 1) as f64 * next as f64 / n as f64,
+                _ => count as f64,
+            },
+        )
+        .collect::<Vec<_>>();
+    let seen_total: f64 = adjusted.iter().sum();
+    adjusted
+        .iter()
+        .zip(row)
+        .map(|(&adjusted, &count)| {
+            if count == 0 {
+                unseen_mass / unseen as f64
+            } else {
+                adjusted / seen_total * (1.0 - unseen_mass)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{good_turing, train, Smoothing};
+    use crate::ngram::Sample;
+
+    fn assert_close(found: &[f64], expected: &[f64]) {
+        assert_eq!(found.len(), expected.len());
+        for (f, e) in found.iter().zip(expected) {
+            assert!((f - e).abs() < 1e-12, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    fn transitions(text: &str, smoothing: Smoothing) -> Vec<Vec<f64>> {
+        let sample = Sample::new(&text.chars().collect::<Vec<_>>());
+        let model = train(&sample, smoothing).unwrap();
+        model.validate().unwrap();
+        model.transitions.unwrap()
+    }
+
+    #[test]
+    fn unsmoothed_rows_are_frequencies() {
+        let rows = transitions("aab", Smoothing::None);
+        assert_close(&rows[0], &[0.5, 0.5]);
+        // `b` is never followed by anything and gets the unigram frequencies
+        assert_close(&rows[1], &[2.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn laplace_counts_every_transition_once_more() {
+        let rows = transitions("aaab", Smoothing::Laplace);
+        assert_close(&rows[0], &[0.6, 0.4]);
+        assert_close(&rows[1], &[0.5, 0.5]);
+    }
+
+    #[test]
+    fn good_turing_gives_unseen_the_mass_of_singles() {
+        // N(1) = 1, N(2) = 1: the single becomes 2 * N(2) / N(1) = 2
+        assert_close(&good_turing(&[2, 1, 0]), &[1.0 / 3.0; 3]);
+        // no N(4) and N(2), counts stay and the unseen share N(1) / N = 2/5
+        assert_close(&good_turing(&[3, 1, 1, 0]), &[0.36, 0.12, 0.12, 0.4]);
+        // nothing but singles falls back to Laplace
+        assert_close(&good_turing(&[1, 1, 0]), &[0.4, 0.4, 0.2]);
+        assert_close(&good_turing(&[2, 2]), &[0.5, 0.5]);
+        // every row of a trained model is validated to sum to 1
+        let rows = transitions("abacabad", Smoothing::GoodTuring);
+        assert!(rows[0][0] > 0.0);
+    }
+
+    #[test]
+    fn empty_sample_is_rejected() {
+        assert!(train(&Sample::new(&[]), Smoothing::None).is_err());
+        assert!(train(&Sample::new(&[]), Smoothing::Laplace).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use generator::model::Model;
+use std::collections::HashMap;
+
+/// States with fewer observations than this are reported but not tested,
+/// the chi-squared approximation is useless on a handful of samples
+const MIN_OBSERVATIONS: usize = 20;
+
+/// Goodness-of-fit of the symbols generated in one state of the source
+#[derive(Debug, Clone)]
+pub struct StateTest {
+    /// Symbols of the context, empty for a memoryless source
+    pub state: Vec<String>,
+    pub observations: usize,
+    pub degrees_of_freedom: usize,
+    pub chi_squared: f64,
+    pub chi_squared_p: f64,
+    pub g: f64,
+    pub g_p: f64,
+    /// A symbol the model gives probability 0 was generated in this state
+    pub impossible: bool,
+}
+
+impl StateTest {
+    pub fn is_tested(&self) -> bool {
+        self.impossible || self.observations >= MIN_OBSERVATIONS
+    }
+    /// Whether the hypothesis that the state follows the model is rejected at `alpha`
+    pub fn rejected(&self, alpha: f64) -> bool {
+        self.impossible || (self.is_tested() && (self.chi_squared_p < alpha || self.g_p < alpha))
+    }
+}
+
+/// Tests the generated symbols of every state against the distribution the
+/// model prescribes for it: the contexts of an order k model, the rows of a
+/// transition matrix or the memoryless probabilities.
+pub fn verify(model: &Model, tokens: &[usize]) -> Vec<StateTest> {
+    let n = model.symbols.len();
+    let contexts = model.indexed_contexts();
+    let listed = contexts
+        .iter()
+        .map(|(context, probabilities)| (context.as_slice(), *probabilities))
+        .collect::<HashMap<_, _>>();
+    let order = model.order();
+
+    // count the symbols following every context of `order` symbols
+    let mut observed = HashMap::<&[usize], Vec<usize>>::new();
+    for window in tokens.windows(order + 1) {
+        observed
+            .entry(&window[..order])
+            .or_insert_with(|| vec![0; n])[window[order]] += 1;
+    }
+    let mut states = observed.into_iter().collect::<Vec<_>>();
+    states.sort();
+    states
+        .into_iter()
+        .map(|(context, counts)| {
+            let expected = match (&model.transitions, listed.get(context)) {
+                (_, Some(&probabilities)) => probabilities,
+                (Some(transitions), None) if order == 1 => &transitions[context[0]],
+                _ => model
+                    .probabilities
+                    .as_deref()
+                    .expect("validated model has a fallback distribution"),
+            };
+            let mut test = goodness_of_fit(&counts, expected);
+            test.state = context
+                .iter()
+                .map(|&symbol| model.symbols[symbol].clone())
+                .collect();
+            test
+        })
+        .collect()
+}
+
+/// Pearson's chi-squared test and the G-test of `observed` counts
+/// against the `expected` probabilities
+pub fn goodness_of_fit(observed: &[usize], expected: &[f64]) -> StateTest {
+    let total: usize = observed.iter().sum();
+    let mut chi_squared = 0.0;
+    let mut g = 0.0;
+    let mut categories = 0;
+    let mut impossible = false;
+    for (&o, &p) in observed.iter().zip(expected) {
+        if p == 0.0 {
+            impossible |= o > 0;
+            continue;
+        }
+        categories += 1;
+        let e = p * total as f64;
+        chi_squared += (o as f64 - e).powi(2) / e;
+        if o > 0 {
+            g += 2.0 * o as f64 * (o as f64 / e).ln();
+        }
+    }
+    let degrees_of_freedom = categories.max(1) - 1;
+    StateTest {
+        state: Vec::new(),
+        observations: total,
+        degrees_of_freedom,
+        chi_squared,
+        chi_squared_p: chi_squared_survival(chi_squared, degrees_of_freedom),
+        g,
+        g_p: chi_squared_survival(g, degrees_of_freedom),
+        impossible,
+    }
+}
+
+/// Probability that a chi-squared variable with `k` degrees of freedom exceeds `x`
+pub fn chi_squared_survival(x: f64, k: usize) -> f64 {
+    if k == 0 {
+        // a single possible symbol leaves nothing to deviate
+        return 1.0;
+    }
+    gamma_q(k as f64 / 2.0, x / 2.0)
+}
+
+/// Regularized upper incomplete gamma function Q(a, x)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        1.0
+    } else if x < a + 1.0 {
+        1.0 - gamma_p_series(a, x)
+    } else {
+        gamma_q_continued_fraction(a, x)
+    }
+}
+
+/// P(a, x) by its power series, converging quickly for x < a + 1
+fn gamma_p_series(a: f64, x: f64) -> f64 {
+    let mut term = 1.0 / a;
+    let mut sum = term;
+    let mut n = a;
+    for _ in 0..1000 {
+        n += 1.0;
+        term *= x / n;
+        sum += term;
+        if term.abs() < sum.abs() * 1e-15 {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+/// Q(a, x) by Lentz's continued fraction, converging quickly for x >= a + 1
+fn gamma_q_continued_fraction(a: f64, x: f64) -> f64 {
+    let tiny = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..1000 {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    (-x + a * x.ln() - ln_gamma(a)).exp() * h
+}
+
+/// ln Γ(x) by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chi_squared_survival, goodness_of_fit};
+
+    #[test]
+    fn survival_matches_tables() {
+        // upper 5% and 0.1% points of the chi-squared distribution
+        for &(x, k, p) in &[
+            (3.841, 1, 0.05),
+            (5.991, 2, 0.05),
+            (18.307, 10, 0.05),
+            (10.828, 1, 0.001),
+            (29.588, 10, 0.001),
+        ] {
+            let survival = chi_squared_survival(x, k);
+            assert!(
+                (survival - p).abs() < p * 1e-3,
+                "Q({}, {}) = {}",
+                x,
+                k,
+                survival
+            );
+        }
+    }
+
+    #[test]
+    fn exact_counts_are_accepted() {
+        let test = goodness_of_fit(&[500, 250, 250], &[0.5, 0.25, 0.25]);
+        assert_eq!(test.chi_squared, 0.0);
+        assert!(!test.rejected(0.01));
+    }
+
+    #[test]
+    fn skewed_counts_are_rejected() {
+        let test = goodness_of_fit(&[700, 150, 150], &[0.5, 0.25, 0.25]);
+        assert!(test.rejected(0.01));
+    }
+
+    #[test]
+    fn impossible_symbols_are_rejected() {
+        let test = goodness_of_fit(&[999, 1], &[1.0, 0.0]);
+        assert!(test.impossible && test.rejected(0.01));
+    }
+}
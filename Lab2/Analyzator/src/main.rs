@@ -1,3 +1,534 @@
-fn main() {
-    println!("Hello, world!");
+use clap::Parser;
+use compression::{
+    ans::NormalizedTable,
+    arithmetic::FrequencyTable,
+    block::{self, BlockTree},
+    container::{self, Codec, Header},
+    context::{self, ContextTrees},
+    dary::DaryTree,
+    huff_encode::Tree,
+    information, lzss,
+    prefix_codes::{self, Codes},
+    tokens::{self, TokenTrees},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+};
+#[derive(Parser)]
+#[clap(version = "1.0", author = "Aleksey S. <siroggi5@gmail.com>")]
+struct Opts {
+    /// Sets the name of the original file, the one given to the Coder
+    #[clap(short, long)]
+    input: String,
+    /// Sets the name of the file produced by the Coder
+    #[clap(short, long)]
+    encoded: String,
+    /// Sets the shared tree the file was coded with, see the Coder's --save-tree
+    #[clap(short, long)]
+    tree: Option<String>,
+    /// Sets the longest tuples the block Huffman codes of the input are reported for
+    #[clap(short = 'k', long, default_value = "4")]
+    max_tuple_len: usize,
+}
+/// The parts of a Coder output: header with the model, and payload
+struct Encoded {
+    coding: Coding,
+    header_bytes: usize,
+    payload_bytes: usize,
+    payload_bits: usize,
+}
+/// The model the payload was coded with
+enum Coding {
+    /// Length of the code of every letter, of Huffman's or another prefix code
+    Prefix(HashMap<char, usize>),
+    /// Digits of the code of every letter, each of log2 `arity` bits
+    Digits {
+        lengths: HashMap<char, usize>,
+        arity: usize,
+    },
+    Arithmetic(FrequencyTable),
+    Ans(NormalizedTable),
+    /// Codes repetitions instead of letters, so a letter has no code of its own
+    Dictionary,
+    /// Codes the symbols of a transform of the letters, which have no code of their own either
+    Transform,
+    /// Codes tuples of this many letters, with a code for the tuple only
+    Tuples(usize),
+    /// Codes every letter with the tree of the letter before it
+    Context(ContextTrees),
+    /// Codes words and other tokens, spelling out those missing from the dictionary
+    Tokens(TokenTrees),
+}
+impl Coding {
+    /// Bits spent on `letter`, -log2 of its probability for arithmetic and ANS coding,
+    /// `None` for letters the model cannot code
+    fn code_length(&self, letter: char) -> Option<f64> {
+        match self {
+            Coding::Prefix(lengths) => lengths.get(&letter).map(|&n| n as f64),
+            Coding::Digits { lengths, arity } => lengths
+                .get(&letter)
+                .map(|&n| n as f64 * (*arity as f64).log2()),
+            Coding::Arithmetic(table) => ideal_length(table.probability(letter)),
+            Coding::Ans(table) => ideal_length(table.probability(letter)),
+            Coding::Dictionary
+            | Coding::Transform
+            | Coding::Tuples(_)
+            | Coding::Context(_)
+            | Coding::Tokens(_) => None,
+        }
+    }
+    fn codes_letters(&self) -> bool {
+        !matches!(
+            self,
+            Coding::Dictionary
+                | Coding::Transform
+                | Coding::Tuples(_)
+                | Coding::Context(_)
+                | Coding::Tokens(_)
+        )
+    }
+}
+fn ideal_length(p: f64) -> Option<f64> {
+    if p > 0.0 {
+        Some(-p.log2())
+    } else {
+        None
+    }
+}
+/// The numbers reported about the original and its Coder output
+struct Report {
+    /// Symbols of the original
+    count: usize,
+    alphabet: usize,
+    /// Entropy H of the frequencies of the symbols, bits/symbol
+    entropy: f64,
+    /// Average code length L, bits/symbol, `None` when the letters have no code of their own
+    average_length: Option<f64>,
+    original_bytes: usize,
+    header_bytes: usize,
+    payload_bytes: usize,
+    payload_bits: usize,
+}
+impl Report {
+    /// Measures the letters counted in `weights` against the codes of `encoded`,
+    /// `name` is the name of the encoded file for the errors
+    fn new(
+        weights: &HashMap<char, usize>,
+        original_bytes: usize,
+        encoded: &Encoded,
+        name: &str,
+    ) -> std::io::Result<Self> {
+        let count: usize = weights.values().sum();
+        let mut probabilities = Vec::with_capacity(weights.len());
+        let mut average_length = 0.0;
+        for (letter, &n) in weights {
+            let p = n as f64 / count as f64;
+            probabilities.push(p);
+            if !encoded.coding.codes_letters() {
+                continue;
+            }
+            let code_length = encoded.coding.code_length(*letter).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{:?} has no code for {:?}, it was encoded from another file",
+                        name, letter
+                    ),
+                )
+            })?;
+            average_length += p * code_length;
+        }
+        Ok(Report {
+            count,
+            alphabet: weights.len(),
+            entropy: information::entropy(&probabilities),
+            average_length: encoded.coding.codes_letters().then_some(average_length),
+            original_bytes,
+            header_bytes: encoded.header_bytes,
+            payload_bytes: encoded.payload_bytes,
+            payload_bits: encoded.payload_bits,
+        })
+    }
+    /// Redundancy of the code L - H, bits/symbol
+    fn redundancy(&self) -> Option<f64> {
+        self.average_length.map(|length| length - self.entropy)
+    }
+    /// Efficiency of the code H/L, `None` as well for an empty original
+    fn efficiency(&self) -> Option<f64> {
+        self.average_length
+            .filter(|&length| length > 0.0)
+            .map(|length| self.entropy / length)
+    }
+    fn encoded_bytes(&self) -> usize {
+        self.header_bytes + self.payload_bytes
+    }
+    /// Shares of the header and of the payload in the encoded file, in percent
+    fn shares(&self) -> (f64, f64) {
+        let total = self.encoded_bytes().max(1) as f64;
+        (
+            100.0 * self.header_bytes as f64 / total,
+            100.0 * self.payload_bytes as f64 / total,
+        )
+    }
+    /// Bits/symbol of the payload and of the whole file, `None` for an empty original
+    fn bits_per_symbol(&self) -> Option<(f64, f64)> {
+        (self.count > 0).then(|| {
+            (
+                self.payload_bits as f64 / self.count as f64,
+                8.0 * self.encoded_bytes() as f64 / self.count as f64,
+            )
+        })
+    }
+    /// Bytes of the original per byte of the encoded file
+    fn compression_ratio(&self) -> f64 {
+        self.original_bytes as f64 / self.encoded_bytes().max(1) as f64
+    }
+}
+/// Counts of the letters of `text`
+fn count_letters(text: &str) -> HashMap<char, usize> {
+    let mut weights = HashMap::new();
+    for letter in text.chars() {
+        *weights.entry(letter).or_insert(0usize) += 1;
+    }
+    weights
+}
+fn main() -> std::io::Result<()> {
+    let opts = Opts::parse();
+    let original = fs::read_to_string(&opts.input)?;
+    let shared = opts.tree.as_ref().map(fs::read).transpose()?;
+    let encoded = read_encoded(&fs::read(&opts.encoded)?, shared.as_deref(), &opts.encoded)?;
+
+    let weights = count_letters(&original);
+    let report = Report::new(&weights, original.len(), &encoded, &opts.encoded)?;
+    let (count, entropy) = (report.count, report.entropy);
+
+    println!("------------------------------------------------------------------------------");
+    println!("Symbols: {}, alphabet of {}", count, report.alphabet);
+    println!("Entropy H = {:.6} bits/symbol", entropy);
+    println!(
+        "Source redundancy 1 - H/log2 n = {:.6}",
+        information::redundancy(entropy, report.alphabet)
+    );
+    if let Some(average_length) = report.average_length {
+        println!("Average code length L = {:.6} bits/symbol", average_length);
+        if let (Some(redundancy), Some(efficiency)) = (report.redundancy(), report.efficiency()) {
+            println!("Redundancy L - H = {:.6} bits/symbol", redundancy);
+            println!("Relative redundancy 1 - H/L = {:.6}", 1.0 - efficiency);
+            println!("Efficiency H/L = {:.6}", efficiency);
+        }
+        if let Coding::Digits { arity, .. } = encoded.coding {
+            let bits = (arity as f64).log2();
+            println!(
+                "In digits of {} levels: L = {:.6}, H/log2 {} = {:.6} digits/symbol",
+                arity,
+                average_length / bits,
+                arity,
+                entropy / bits
+            );
+        }
+    } else if let Coding::Context(trees) = &encoded.coding {
+        report_context(&original, trees, &opts.encoded)?;
+    } else {
+        match encoded.coding {
+            Coding::Transform => print!("BWT coding"),
+            Coding::Tuples(k) => print!("Huffman coding of {}-tuples", k),
+            Coding::Tokens(trees) => {
+                let dictionary = trees.token_codes();
+                let escaped = tokens::tokenize(&original)
+                    .filter(|token| !dictionary.contains_key(*token))
+                    .count();
+                let words = dictionary.len() - dictionary.contains_key(tokens::ESCAPE) as usize;
+                println!(
+                    "Tokens: {}, dictionary of {}, {} spelled out",
+                    tokens::tokenize(&original).count(),
+                    words,
+                    escaped
+                );
+                print!("Huffman coding of words");
+            }
+            _ => print!("Dictionary coding"),
+        }
+        println!(", the payload bits/symbol below can beat H");
+    }
+    // a tree needs a letter, an empty input has none
+    if !weights.is_empty() {
+        println!("------------------------------------------------------------------------------");
+        println!("Prefix codes built from the input:");
+        compare_prefix_codes(&weights, count, entropy);
+        println!("------------------------------------------------------------------------------");
+        println!("Block Huffman codes built from the input, per symbol:");
+        for k in 1..=opts.max_tuple_len.clamp(1, block::MAX_TUPLE_LEN) {
+            let tree = BlockTree::build(&original, k)?;
+            let average_length = tree.bits_per_letter(&original);
+            let block_entropy = block::block_entropy(&original, k);
+            // the dictionary of tuples grows with k and is paid for in the header
+            let model = tree.to_bytes().len() as f64 * 8.0 / count as f64;
+            println!(
+                "k = {:<2} L/k = {:.6}, H_k/k = {:.6}, L/k - H_k/k = {:.6}, model = {:.6} bits/symbol",
+                k,
+                average_length,
+                block_entropy,
+                average_length - block_entropy,
+                model
+            );
+        }
+    }
+    println!("------------------------------------------------------------------------------");
+    let (header_share, payload_share) = report.shares();
+    println!(
+        "Header: {} bytes ({:.2}%), payload: {} bytes ({:.2}%)",
+        report.header_bytes, header_share, report.payload_bytes, payload_share
+    );
+    // an empty input has no bits per symbol
+    if let Some((payload, whole)) = report.bits_per_symbol() {
+        println!(
+            "Payload: {:.6} bits/symbol, whole file: {:.6} bits/symbol",
+            payload, whole
+        );
+    }
+    println!(
+        "Original: {} bytes, encoded: {} bytes, compression ratio {:.4}",
+        report.original_bytes,
+        report.encoded_bytes(),
+        report.compression_ratio()
+    );
+    Ok(())
+}
+/// Prints the conditional entropy of the letters given the one before them,
+/// and how close their order-1 codes come to it
+fn report_context(text: &str, trees: &ContextTrees, name: &str) -> std::io::Result<()> {
+    let bigrams = context::build_weights(text);
+    let pairs: usize = bigrams.values().sum();
+    if pairs == 0 {
+        return Ok(());
+    }
+    let mut befores = HashMap::new();
+    let mut bits = 0;
+    for (&(before, letter), &n) in &bigrams {
+        *befores.entry(before).or_insert(0) += n;
+        let code_length = trees.code_length(before, letter).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?} has no code for {:?} after {:?}, it was encoded from another file",
+                    name, letter, before
+                ),
+            )
+        })?;
+        bits += n * code_length;
+    }
+    // H(A|A^) = H(A^, A) - H(A^)
+    let conditional = information::entropy_of_counts(bigrams.into_values())
+        - information::entropy_of_counts(befores.into_values());
+    let average_length = bits as f64 / pairs as f64;
+    println!(
+        "Conditional entropy H(A|A^) = {:.6} bits/symbol",
+        conditional
+    );
+    println!(
+        "Average code length after the first symbol L = {:.6} bits/symbol",
+        average_length
+    );
+    println!(
+        "Redundancy L - H(A|A^) = {:.6} bits/symbol",
+        average_length - conditional
+    );
+    println!("Efficiency H(A|A^)/L = {:.6}", conditional / average_length);
+    Ok(())
+}
+/// Prints the average length of Huffman's and the older prefix codes of the letters counted in `weights`
+fn compare_prefix_codes(weights: &HashMap<char, usize>, count: usize, entropy: f64) {
+    let mut huffman = Tree::build_from_weights(weights.clone());
+    huffman.assign_codes();
+    for (name, codes) in [
+        ("Huffman", huffman.read_codes()),
+        ("Shannon-Fano", prefix_codes::shannon_fano(weights)),
+        ("Shannon", prefix_codes::shannon(weights)),
+        (
+            "Shannon-Fano-Elias",
+            prefix_codes::shannon_fano_elias(weights),
+        ),
+    ] {
+        let average_length = expected_length(&codes, weights, count);
+        println!(
+            "{:<18} L = {:.6}, L - H = {:.6} bits/symbol",
+            name,
+            average_length,
+            average_length - entropy
+        );
+    }
+}
+/// Average length of `codes` over the letters counted in `weights`
+fn expected_length(codes: &Codes, weights: &HashMap<char, usize>, count: usize) -> f64 {
+    weights
+        .iter()
+        .map(|(letter, &n)| codes[letter].len() as f64 * n as f64 / count as f64)
+        .sum()
+}
+/// Splits the Coder output into its header and payload, reading the model the same way the DeCoder does
+fn read_encoded(bytes: &[u8], shared: Option<&[u8]>, name: &str) -> std::io::Result<Encoded> {
+    let in_file = |err: Error| Error::new(err.kind(), format!("{:?} {}", name, err));
+    let (header, header_bytes) = Header::read(bytes).map_err(in_file)?;
+    let model = header.resolve_model(shared).map_err(in_file)?;
+    let invalid_model = |_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{:?} stores an invalid model", name),
+        )
+    };
+    let coding = match header.codec {
+        Codec::Huffman => {
+            let tree = container::huffman_tree(model).map_err(invalid_model)?;
+            Coding::Prefix(
+                tree.read_codes()
+                    .into_iter()
+                    .map(|(letter, code)| (letter, code.len()))
+                    .collect(),
+            )
+        }
+        Codec::Dary => {
+            let tree = DaryTree::<char>::from_bytes(model).map_err(invalid_model)?;
+            Coding::Digits {
+                lengths: tree
+                    .read_codes()
+                    .into_iter()
+                    .map(|(letter, code)| (letter, code.len()))
+                    .collect(),
+                arity: tree.arity(),
+            }
+        }
+        Codec::CodeTable => Coding::Prefix(
+            prefix_codes::from_bytes::<char>(model)
+                .map_err(invalid_model)?
+                .into_iter()
+                .map(|(letter, code)| (letter, code.len()))
+                .collect(),
+        ),
+        Codec::Arithmetic => {
+            Coding::Arithmetic(FrequencyTable::from_bytes(model).map_err(invalid_model)?)
+        }
+        Codec::Rans | Codec::Tans => {
+            Coding::Ans(NormalizedTable::from_bytes(model).map_err(invalid_model)?)
+        }
+        Codec::Lzss => {
+            lzss::Trees::from_bytes(model).map_err(invalid_model)?;
+            Coding::Dictionary
+        }
+        Codec::Lz78 | Codec::Lzw => {
+            container::dictionary_bits(model).map_err(invalid_model)?;
+            Coding::Dictionary
+        }
+        Codec::Context => Coding::Context(ContextTrees::from_bytes(model).map_err(invalid_model)?),
+        Codec::Tokens => Coding::Tokens(TokenTrees::from_bytes(model).map_err(invalid_model)?),
+        Codec::Block => Coding::Tuples(BlockTree::from_bytes(model).map_err(invalid_model)?.k),
+        Codec::Bwt => {
+            container::symbol_tree::<u16>(model).map_err(invalid_model)?;
+            Coding::Transform
+        }
+    };
+    let payload_bytes = bytes.len() - header_bytes;
+    Ok(Encoded {
+        coding,
+        header_bytes,
+        payload_bytes,
+        payload_bits: (payload_bytes * 8).saturating_sub(header.padding_bits as usize),
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compression::{container::Model, huff_tree};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    /// Coder output of `text` with an embedded Huffman tree
+    fn huffman_file(text: &str) -> Vec<u8> {
+        let mut tree = Tree::build_from_weights(count_letters(text));
+        tree.assign_codes();
+        let model = container::huffman_model(tree.as_bin());
+        let tree = container::huffman_tree(&model).unwrap();
+        let (payload, padding_bits, _) = huff_tree::compress_with_tree(text.to_string(), tree)
+            .unwrap()
+            .into_inner();
+        let mut bytes = Header {
+            codec: Codec::Huffman,
+            model: Model::Embedded(model),
+            padding_bits,
+            original_len: Some(text.len() as u32),
+        }
+        .to_bytes();
+        bytes.extend(payload);
+        bytes
+    }
+
+    #[test]
+    fn huffman_of_a_dyadic_source() {
+        // p = 1/2, 1/4, 1/4, Huffman meets the entropy of 1.5 bits
+        let text = "aaaabbcc";
+        let bytes = huffman_file(text);
+        let encoded = read_encoded(&bytes, None, "test").unwrap();
+        let report = Report::new(&count_letters(text), text.len(), &encoded, "test").unwrap();
+        assert_eq!((report.count, report.alphabet), (8, 3));
+        assert!(close(report.entropy, 1.5));
+        assert!(close(report.average_length.unwrap(), 1.5));
+        assert!(close(report.redundancy().unwrap(), 0.0));
+        assert!(close(report.efficiency().unwrap(), 1.0));
+        // 12 bits of payload in 2 bytes
+        assert_eq!(report.payload_bits, 12);
+        assert_eq!(report.encoded_bytes(), bytes.len());
+        let (header, payload) = report.shares();
+        assert!(close(header + payload, 100.0));
+        let (payload, whole) = report.bits_per_symbol().unwrap();
+        assert!(close(payload, 1.5));
+        assert!(close(whole, 8.0 * bytes.len() as f64 / 8.0));
+        assert!(close(report.compression_ratio(), 8.0 / bytes.len() as f64));
+    }
+
+    #[test]
+    fn efficiency_is_entropy_over_length() {
+        let text = "aaaaaaab";
+        let encoded = Encoded {
+            coding: Coding::Prefix(HashMap::from([('a', 1), ('b', 2)])),
+            header_bytes: 10,
+            payload_bytes: 2,
+            payload_bits: 9,
+        };
+        let report = Report::new(&count_letters(text), text.len(), &encoded, "test").unwrap();
+        let entropy = information::entropy(&[7.0 / 8.0, 1.0 / 8.0]);
+        assert!(close(report.entropy, entropy));
+        assert!(close(report.average_length.unwrap(), 9.0 / 8.0));
+        assert!(close(report.efficiency().unwrap(), entropy / (9.0 / 8.0)));
+        assert!(close(report.redundancy().unwrap(), 9.0 / 8.0 - entropy));
+        assert_eq!(report.encoded_bytes(), 12);
+        // a letter without a code comes from another file
+        assert!(Report::new(&count_letters("abc"), 3, &encoded, "test").is_err());
+    }
+
+    #[test]
+    fn empty_original_has_no_rates() {
+        let encoded = Encoded {
+            coding: Coding::Prefix(HashMap::new()),
+            header_bytes: 16,
+            payload_bytes: 0,
+            payload_bits: 0,
+        };
+        let report = Report::new(&count_letters(""), 0, &encoded, "test").unwrap();
+        assert_eq!(report.count, 0);
+        assert_eq!(report.efficiency(), None);
+        assert_eq!(report.bits_per_symbol(), None);
+        assert_eq!(report.shares(), (100.0, 0.0));
+        assert_eq!(report.compression_ratio(), 0.0);
+        // the letters of a dictionary coder have no code of their own
+        let encoded = Encoded {
+            coding: Coding::Dictionary,
+            ..encoded
+        };
+        let report = Report::new(&count_letters("ab"), 2, &encoded, "test").unwrap();
+        assert_eq!(report.average_length, None);
+        assert_eq!(report.redundancy(), None);
+    }
 }
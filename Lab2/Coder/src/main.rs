@@ -1,13 +1,23 @@
-use bitvec::{bits, bitvec, macros::internal::funty::IsNumber, order::Msb0, prelude::BitVec};
-use clap::Parser;
-use huff_tree::huff_tree::Tree;
-mod huff_tree;
+use clap::{ArgEnum, Parser};
+use compression::{
+    ans::{self, NormalizedTable},
+    arithmetic::{self, FrequencyTable},
+    block::{self, BlockTree},
+    bwt,
+    container::{self, Codec, Header, Model},
+    context::{self, ContextTrees},
+    dary::{self, DaryTree},
+    huff_encode::{self, Tree},
+    huff_tree, lz78, lzss, prefix_codes,
+    tokens::{self, TokenTrees},
+};
+use serde::Deserialize;
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{BufReader, BufWriter, Error, Read, Seek, SeekFrom, Write},
-    path::PathBuf,
-    vec,
+    convert::TryFrom,
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
 };
 #[derive(Parser)]
 #[clap(version = "1.0", author = "Aleksey S. <siroggi5@gmail.com>")]
@@ -18,110 +28,486 @@ struct Opts {
     /// Sets the name of the output file
     #[clap(short, long)]
     output: String,
+    /// Builds the tree from a frequency table written by `check_for_generator stats`
+    /// (.csv or .json) instead of from the input, the pairs of chars for context, not for the
+    /// dictionary codecs
+    #[clap(short, long, conflicts_with = "tree")]
+    weights: Option<String>,
+    /// Codes with a shared tree, or frequency table for the other codecs, saved earlier with
+    /// --save-tree, the output only refers to it by id and needs the same file to be decoded
+    #[clap(short, long)]
+    tree: Option<String>,
+    /// Saves the tree, or frequency table for the other codecs, to share it between files,
+    /// the output then refers to it by id
+    #[clap(short, long, conflicts_with = "tree")]
+    save_tree: Option<String>,
+    /// Sets how the characters are coded
+    #[clap(short, long, arg_enum, default_value = "huffman")]
+    codec: CodecArg,
+    /// Sets how many bytes back lzss looks for repetitions
+    #[clap(long, default_value = "32768", validator(window_size))]
+    window: usize,
+    /// Sets the bits of the largest lz78 and lzw index, the dictionary is reset when it is full
+    #[clap(long, default_value = "16", validator(max_bits))]
+    max_bits: u8,
+    /// Sets how many bytes bwt sorts at once, larger blocks compress better but slower
+    #[clap(long, default_value = "900000", validator(block_size))]
+    block_size: usize,
+    /// Codes tuples of k characters as one with huffman, the last one shorter when k does not divide the input
+    #[clap(short = 'k', long, default_value = "1", validator(tuple_len))]
+    tuple_len: usize,
+    /// Codes with d-ary huffman codes of digits 0..d, for channels of d levels, packed into bytes
+    #[clap(short = 'd', long, default_value = "2", validator(arity))]
+    arity: usize,
+    /// Sets how many times words need to occur to be in the dictionary, the others are spelled out
+    #[clap(long, default_value = "2", validator(min_count))]
+    min_count: usize,
+}
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CodecArg {
+    /// Huffman codes, a whole number of bits per character
+    Huffman,
+    /// Arithmetic coding, close to the entropy even below 1 bit per character
+    Arith,
+    /// Range asymmetric numeral systems, as close as arith and faster
+    Rans,
+    /// Table asymmetric numeral systems, rANS with the arithmetic in lookup tables
+    Tans,
+    /// LZSS matches with Huffman coded literals, lengths and distances, as in deflate
+    Lzss,
+    /// LZ78 pairs of a dictionary index and the next byte, no model at all
+    Lz78,
+    /// LZW dictionary indices only, no model at all
+    Lzw,
+    /// Burrows-Wheeler transform, move-to-front and zero-run RLE before Huffman codes, as in bzip2
+    Bwt,
+    /// Shannon-Fano codes, splitting the letters into halves of about the same weight
+    ShannonFano,
+    /// Shannon codes, ceil(-log2 p) bits per character
+    Shannon,
+    /// Shannon-Fano-Elias codes, one bit more than Shannon's but with no sorting
+    Sfe,
+    /// Order-1 Huffman codes, a tree for every preceding character
+    Context,
+    /// Huffman codes of words, whitespace runs and punctuation, with the dictionary in the header
+    Words,
+}
+impl From<CodecArg> for Codec {
+    fn from(codec: CodecArg) -> Self {
+        match codec {
+            CodecArg::Huffman => Codec::Huffman,
+            CodecArg::Arith => Codec::Arithmetic,
+            CodecArg::Rans => Codec::Rans,
+            CodecArg::Tans => Codec::Tans,
+            CodecArg::Lzss => Codec::Lzss,
+            CodecArg::Lz78 => Codec::Lz78,
+            CodecArg::Lzw => Codec::Lzw,
+            CodecArg::Bwt => Codec::Bwt,
+            CodecArg::Context => Codec::Context,
+            CodecArg::Words => Codec::Tokens,
+            CodecArg::ShannonFano | CodecArg::Shannon | CodecArg::Sfe => Codec::CodeTable,
+        }
+    }
+}
+/// One row of the frequency table, n-grams of another length than needed are skipped
+#[derive(Deserialize)]
+struct Row {
+    ngram: String,
+    count: usize,
+}
+fn window_size(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if (1..=lzss::MAX_WINDOW).contains(&n) => Ok(()),
+        _ => Err(format!("window needed to be in 1..={}", lzss::MAX_WINDOW)),
+    }
+}
+fn block_size(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if (1..=bwt::MAX_BLOCK).contains(&n) => Ok(()),
+        _ => Err(format!("block size needed to be in 1..={}", bwt::MAX_BLOCK)),
+    }
+}
+fn tuple_len(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if (1..=block::MAX_TUPLE_LEN).contains(&n) => Ok(()),
+        _ => Err(format!(
+            "tuple length needed to be in 1..={}",
+            block::MAX_TUPLE_LEN
+        )),
+    }
+}
+fn arity(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if (2..=dary::MAX_ARITY).contains(&n) => Ok(()),
+        _ => Err(format!("arity needed to be in 2..={}", dary::MAX_ARITY)),
+    }
+}
+fn min_count(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(()),
+        _ => Err("min count needed to be at least 1".to_string()),
+    }
+}
+fn max_bits(val: &str) -> Result<(), String> {
+    match val.parse::<u8>() {
+        Ok(n) if (lz78::MIN_BITS..=lz78::MAX_BITS).contains(&n) => Ok(()),
+        _ => Err(format!(
+            "max bits needed to be in {}..={}",
+            lz78::MIN_BITS,
+            lz78::MAX_BITS
+        )),
+    }
 }
 fn main() -> std::io::Result<()> {
-    let opts = Opts::parse();
-    let mut src = File::open(opts.input)?;
-    let mut chars = String::new();
-    src.read_to_string(&mut chars)?;
-    let mut reader = BufReader::new(src);
-
-    // write to dst file
-    let dst = File::create(opts.output)?;
-    let mut writer = BufWriter::new(dst);
-
-    // allocate a u8 buffer of size == block_size
-
-    let weights = huff_tree::huff_tree::build_weights(chars.as_str());
-    let mut tree = huff_tree::huff_tree::Tree::build_from_weights(weights);
-    tree.assign_codes();
-    let tree_bin = tree.as_bin();
-    let tree_bin_padding = huff_tree::huff_tree::calc_padding_bits(tree_bin.len());
-    let tree_bin_bytes = tree_bin.into_vec();
-    // return reader to start
-    reader.seek(SeekFrom::Start(0))?;
-
-    // write an empty byte, later to be filled by padding data
-    writer.write_all(&[0])?;
-    // write the tree_bin_bytes lenght as a 4 byte num
-    writer.write_all(&(tree_bin_bytes.len() as u32).to_be_bytes())?;
-    // write the HuffTree represented as bytes
-    writer.write_all(&tree_bin_bytes)?;
-    // compress and write compressed bytes, returning the number of bits used as padding
-    let comp_padding = compress_to_writer(&mut reader, &mut writer, tree)?;
+    run(Opts::parse())
+}
+/// Codes the input as asked by `opts` and writes the header and payload
+fn run(opts: Opts) -> std::io::Result<()> {
+    let chars = fs::read_to_string(&opts.input)?;
+    // u32::MAX stands for an unknown length
+    let original_len = u32::try_from(chars.len())
+        .ok()
+        .filter(|&len| len != u32::MAX)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{:?} is too large for the container", opts.input),
+            )
+        })?;
 
-    // return to the start of the file and set the padding bits
-    writer.seek(SeekFrom::Start(0))?;
-    writer.write_all(&[(tree_bin_padding << 4) + comp_padding])?;
+    let codec = match opts.codec {
+        CodecArg::Huffman if opts.tuple_len > 1 && opts.arity > 2 => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "tuples are only coded by binary huffman".to_string(),
+            ))
+        }
+        CodecArg::Huffman if opts.tuple_len > 1 => Codec::Block,
+        CodecArg::Huffman if opts.arity > 2 => Codec::Dary,
+        _ if opts.tuple_len > 1 => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "tuples are only coded by huffman".to_string(),
+            ))
+        }
+        _ if opts.arity > 2 => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "d-ary codes are only built by huffman".to_string(),
+            ))
+        }
+        codec => Codec::from(codec),
+    };
+    // the dictionary codecs and bwt build their model from the parsed or transformed input
+    let tokens = match codec {
+        Codec::Lzss => {
+            let options = lzss::Options {
+                window: opts.window,
+                ..lzss::Options::default()
+            };
+            lzss::tokenize(chars.as_bytes(), &options)
+        }
+        _ => Vec::new(),
+    };
+    let blocks = match codec {
+        Codec::Bwt => bwt::transform(chars.as_bytes(), opts.block_size),
+        _ => Vec::new(),
+    };
+    let codes_chars = matches!(
+        codec,
+        Codec::Huffman
+            | Codec::Arithmetic
+            | Codec::Rans
+            | Codec::Tans
+            | Codec::CodeTable
+            | Codec::Dary
+    );
+    let model = match &opts.tree {
+        Some(path) => fs::read(path)?,
+        None if codec == Codec::Context => {
+            let bigrams = match &opts.weights {
+                Some(path) => read_bigrams(path)?,
+                None => context::build_weights(&chars),
+            };
+            ContextTrees::build(&bigrams)?.to_bytes()
+        }
+        None if !codes_chars => {
+            if opts.weights.is_some() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a frequency table is only of use to the codecs of single characters"
+                        .to_string(),
+                ));
+            }
+            match codec {
+                Codec::Lzss => lzss::Trees::build(&tokens).to_bytes(),
+                Codec::Bwt => container::huffman_model(bwt::build_tree(&blocks).as_bin()),
+                Codec::Block => BlockTree::build(&chars, opts.tuple_len)?.to_bytes(),
+                Codec::Tokens => TokenTrees::build(&chars, opts.min_count)?.to_bytes(),
+                _ => container::dictionary_model(opts.max_bits),
+            }
+        }
+        None => {
+            let weights = match &opts.weights {
+                Some(path) => read_weights(path)?,
+                None => huff_encode::build_weights(&chars),
+            };
+            match codec {
+                Codec::Huffman => {
+                    let mut tree = Tree::build_from_weights(weights);
+                    tree.assign_codes();
+                    container::huffman_model(tree.as_bin())
+                }
+                Codec::Dary => DaryTree::build_from_weights(weights, opts.arity)?.to_bytes(),
+                Codec::Arithmetic => FrequencyTable::new(weights).to_bytes(),
+                Codec::Rans | Codec::Tans => NormalizedTable::new(weights)?.to_bytes(),
+                Codec::CodeTable => prefix_codes::to_bytes(&match opts.codec {
+                    CodecArg::ShannonFano => prefix_codes::shannon_fano(&weights),
+                    CodecArg::Shannon => prefix_codes::shannon(&weights),
+                    _ => prefix_codes::shannon_fano_elias(&weights),
+                }),
+                _ => unreachable!(),
+            }
+        }
+    };
+    if let Some(path) = &opts.save_tree {
+        fs::write(path, &model)?;
+    }
+    let not_built_for = |err: Error| {
+        Error::new(
+            err.kind(),
+            format!("{}, the model was not built for {:?}", err, opts.input),
+        )
+    };
+    let (payload, padding_bits) = match codec {
+        Codec::Huffman => {
+            let tree = container::huffman_tree(&model)?;
+            let (payload, padding_bits, _) = huff_tree::compress_with_tree(chars, tree)
+                .map_err(not_built_for)?
+                .into_inner();
+            (payload, padding_bits)
+        }
+        Codec::Arithmetic => arithmetic::compress(&chars, &FrequencyTable::from_bytes(&model)?)
+            .map_err(not_built_for)?,
+        Codec::Rans => (
+            ans::rans_compress(&chars, &NormalizedTable::from_bytes(&model)?)
+                .map_err(not_built_for)?,
+            0,
+        ),
+        Codec::Tans => ans::tans_compress(&chars, &NormalizedTable::from_bytes(&model)?)
+            .map_err(not_built_for)?,
+        Codec::Lzss => {
+            lzss::compress(&tokens, &lzss::Trees::from_bytes(&model)?).map_err(not_built_for)?
+        }
+        Codec::Lz78 => lz78::lz78_compress(chars.as_bytes(), container::dictionary_bits(&model)?)?,
+        Codec::Lzw => lz78::lzw_compress(chars.as_bytes(), container::dictionary_bits(&model)?)?,
+        Codec::CodeTable => {
+            prefix_codes::compress(chars.chars(), &prefix_codes::from_bytes(&model)?)
+                .map_err(not_built_for)?
+        }
+        Codec::Dary => (
+            dary::compress(chars.chars(), &DaryTree::from_bytes(&model)?).map_err(not_built_for)?,
+            0,
+        ),
+        Codec::Context => {
+            context::compress(&chars, &ContextTrees::from_bytes(&model)?).map_err(not_built_for)?
+        }
+        Codec::Tokens => {
+            tokens::compress(&chars, &TokenTrees::from_bytes(&model)?).map_err(not_built_for)?
+        }
+        Codec::Block => {
+            let block = BlockTree::from_bytes(&model)?;
+            prefix_codes::compress(block::tuples(&chars, block.k), &block.tree.read_codes())
+                .map_err(not_built_for)?
+        }
+        Codec::Bwt => {
+            bwt::compress(&blocks, &container::symbol_tree(&model)?).map_err(not_built_for)?
+        }
+    };
 
-    writer.flush()?;
+    let header = Header {
+        codec,
+        model: if opts.tree.is_some() || opts.save_tree.is_some() {
+            Model::Shared(container::model_id(&model))
+        } else {
+            Model::Embedded(model)
+        },
+        padding_bits,
+        original_len: Some(original_len),
+    };
+    let mut bytes = header.to_bytes();
+    bytes.extend(payload);
+    fs::write(opts.output, bytes)?;
     Ok(())
 }
-fn compress_to_writer<R: Read, W: Write + Seek>(
-    reader: &mut R,
-    writer: &mut W,
-    tree: Tree,
-) -> Result<u8, Error> {
-    let tree = tree;
+/// Reads the rows of a `;`-separated CSV or a JSON frequency table
+fn read_rows(path: &str) -> std::io::Result<Vec<Row>> {
+    let invalid = |err: &dyn std::fmt::Display| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{:?} is not a frequency table: {}", path, err),
+        )
+    };
+    if Path::new(path).extension() == Some("csv".as_ref()) {
+        csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .from_path(path)?
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|err| invalid(&err))
+    } else {
+        serde_json::from_slice(&fs::read(path)?).map_err(|err| invalid(&err))
+    }
+}
+fn no_rows(path: &str, length: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "{:?} is not a frequency table: it has no {} rows",
+            path, length
+        ),
+    )
+}
+/// Reads the counts of single chars from a frequency table
+fn read_weights(path: &str) -> std::io::Result<HashMap<char, usize>> {
+    let mut weights = HashMap::new();
+    for row in read_rows(path)? {
+        let mut letters = row.ngram.chars();
+        if let (Some(letter), None) = (letters.next(), letters.next()) {
+            *weights.entry(letter).or_insert(0) += row.count;
+        }
+    }
+    if weights.is_empty() {
+        return Err(no_rows(path, "single-char"));
+    }
+    Ok(weights)
+}
+/// Reads the counts of pairs of chars from a frequency table, see `check_for_generator stats -n 2`
+fn read_bigrams(path: &str) -> std::io::Result<HashMap<(char, char), usize>> {
+    let mut bigrams = HashMap::new();
+    for row in read_rows(path)? {
+        let mut letters = row.ngram.chars();
+        if let (Some(before), Some(letter), None) = (letters.next(), letters.next(), letters.next())
+        {
+            *bigrams.entry((before, letter)).or_insert(0) += row.count;
+        }
+    }
+    if bigrams.is_empty() {
+        return Err(no_rows(path, "two-char"));
+    }
+    Ok(bigrams)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
 
-    let prev_byte = 0;
-    let mut prev_padding = 0;
-    println!("{:#?}", tree);
-    /// compress the buffer into CompressData, combining it with
-    /// the prev_byte if the prev_padding != 0
-    macro_rules! comp_data_from {
-        ($buf:expr) => {{
-            // get and own the compress data
-            let (mut comp_bytes, padding_bits, huff_tree) =
-                huff_tree::huff_tree::compress_with_tree($buf, tree.clone())
-                    .unwrap()
-                    .into_inner();
-            // if the previous compress data's padding isn't 0
-            // write the comp_bytes minding the padding
-            if prev_padding != 0 {
-                writer.seek(SeekFrom::Current(-1)).unwrap();
+    /// A directory of its own for every test, in the temporary directory
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("coder-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-                comp_bytes = offset_bytes(&comp_bytes, prev_padding as usize);
-                comp_bytes[0] |= prev_byte
-            }
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).to_str().unwrap().to_string()
+    }
 
-            (comp_bytes, padding_bits, huff_tree)
-        }};
-    }
-    let mut tro = String::new();
-    reader.read_to_string(&mut tro)?;
-    // try to read exactly buf.len() bytes, compressing them and repeating
-    let (comp_bytes, padding_bits, _huff_tree) = comp_data_from!(tro);
-    writer.write_all(&comp_bytes)?;
-    prev_padding = padding_bits;
+    fn code(args: &[&str]) -> std::io::Result<()> {
+        run(Opts::try_parse_from(std::iter::once("Coder").chain(args.iter().copied())).unwrap())
+    }
 
-    // return the written compressed data's padding bits
-    Ok(prev_padding)
-}
-fn offset_bytes(bytes: &[u8], n: usize) -> Vec<u8> {
-    let empty_bytes = n / 8;
-    let mut offset_bytes = vec![0; empty_bytes];
-    offset_bytes.reserve_exact(bytes.len());
+    /// Decodes a Huffman coded file the way the DeCoder does
+    fn decode(bytes: &[u8], shared: Option<&[u8]>) -> std::io::Result<String> {
+        let (header, len) = Header::read(bytes)?;
+        let tree = container::huffman_tree(header.resolve_model(shared)?)?;
+        Ok(huff_tree::decompress(
+            &bytes[len..],
+            header.padding_bits,
+            &tree,
+        ))
+    }
 
-    let mut comp_byte = 0b0000_0000;
-    let mut bit_ptr = (7 - n) % 8;
-    for byte in bytes {
-        for i in 0..8 {
-            comp_byte |= (((byte >> (7 - i)) & 1 == 1) as u8) << bit_ptr;
+    const WEIGHTS: &str = r#"[
+        {"ngram": "a", "count": 5, "probability": 0.5},
+        {"ngram": "b", "count": 2, "probability": 0.2},
+        {"ngram": "r", "count": 2, "probability": 0.2},
+        {"ngram": "c", "count": 1, "probability": 0.1},
+        {"ngram": "d", "count": 1, "probability": 0.1},
+        {"ngram": "ab", "count": 2, "probability": 0.5}
+    ]"#;
 
-            if bit_ptr == 0 {
-                offset_bytes.push(comp_byte);
-                comp_byte = 0b0000_0000;
-                bit_ptr = 7;
-            } else {
-                bit_ptr -= 1
-            };
-        }
+    #[test]
+    fn weight_tables_keep_rows_of_the_length_needed() {
+        let dir = scratch("rows");
+        let json = path(&dir, "weights.json");
+        fs::write(&json, WEIGHTS).unwrap();
+        let weights = read_weights(&json).unwrap();
+        assert_eq!(weights.len(), 5);
+        assert_eq!(weights[&'a'], 5);
+        let csv = path(&dir, "weights.csv");
+        fs::write(
+            &csv,
+            "ngram;count;probability\nab;2;0.4\nba;3;0.6\nab;1;0.2\n",
+        )
+        .unwrap();
+        let bigrams = read_bigrams(&csv).unwrap();
+        assert_eq!(bigrams[&('a', 'b')], 3);
+        assert_eq!(bigrams[&('b', 'a')], 3);
+        let err = read_weights(&csv).unwrap_err();
+        assert!(err.to_string().contains("no single-char rows"), "{}", err);
+        fs::write(&json, "not a table").unwrap();
+        assert_eq!(
+            read_weights(&json).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        fs::remove_dir_all(dir).unwrap();
     }
-    let padding_bits = if bit_ptr == 7 { 0 } else { bit_ptr + 1 };
-    if padding_bits != 0 {
-        offset_bytes.push(comp_byte);
+
+    #[test]
+    fn shared_tree_from_weights_roundtrip() {
+        let dir = scratch("shared");
+        let (weights, tree) = (path(&dir, "weights.json"), path(&dir, "tree.bin"));
+        let (input, output) = (path(&dir, "input.txt"), path(&dir, "output.bin"));
+        fs::write(&weights, WEIGHTS).unwrap();
+        fs::write(&input, "abracadabra").unwrap();
+        code(&["-i", &input, "-o", &output, "-w", &weights, "-s", &tree]).unwrap();
+        let model = fs::read(&tree).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        let (header, len) = Header::read(&bytes).unwrap();
+        assert_eq!(header.model, Model::Shared(container::model_id(&model)));
+        // the id alone, no model
+        assert_eq!(len, container::SHARED_HEADER_LEN);
+        assert_eq!(header.original_len, Some(11));
+        assert_eq!(decode(&bytes, Some(&model)).unwrap(), "abracadabra");
+
+        // another file coded with the saved tree refers to the same id
+        fs::write(&input, "cab").unwrap();
+        code(&["-i", &input, "-o", &output, "-t", &tree]).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        assert_eq!(Header::read(&bytes).unwrap().0.model, header.model);
+        assert_eq!(decode(&bytes, Some(&model)).unwrap(), "cab");
+        fs::remove_dir_all(dir).unwrap();
     }
 
-    offset_bytes
+    #[test]
+    fn shared_tree_has_to_match() {
+        let dir = scratch("mismatch");
+        let (weights, tree) = (path(&dir, "weights.json"), path(&dir, "tree.bin"));
+        let (input, output) = (path(&dir, "input.txt"), path(&dir, "output.bin"));
+        fs::write(&weights, WEIGHTS).unwrap();
+        fs::write(&input, "abracadabra").unwrap();
+        code(&["-i", &input, "-o", &output, "-w", &weights, "-s", &tree]).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        let err = decode(&bytes, None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let mut other = fs::read(&tree).unwrap();
+        other.push(0);
+        let err = decode(&bytes, Some(&other)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        // a letter missing from the shared tree cannot be coded
+        fs::write(&input, "abc xyz").unwrap();
+        assert!(code(&["-i", &input, "-o", &output, "-t", &tree]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
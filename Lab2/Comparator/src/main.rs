@@ -1,53 +1,353 @@
-use std::{fs::File, io::Read};
+use std::{
+    fmt::Debug,
+    fs,
+    io::{Error, ErrorKind, Write},
+};
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+/// Largest codeword, the histogram of flipped bits has a slot for every bit of it
+const MAX_BLOCK: usize = 1 << 16;
 #[derive(Parser)]
 #[clap(version = "1.0", author = "Aleksey S. <siroggi5@gmail.com>")]
 struct Opts {
-    /// Sets the name of the input file, with characters to code
+    /// Sets the name of the first file
     #[clap(short, long)]
     one_input: String,
-    /// Sets the name of the output file
+    /// Sets the name of the second file
     #[clap(short, long)]
     two_input: String,
+    /// Sets the units the files are compared in
+    #[clap(short, long, arg_enum, default_value = "char")]
+    mode: Mode,
+    /// Sets how many of the first differences are listed
+    #[clap(short, long, default_value = "10")]
+    first: usize,
+    /// Sets the size in bytes of the codewords, e.g. 8 for the noise-resistant coder,
+    /// and prints how many of them got 0, 1, 2... flipped bits
+    #[clap(short, long, validator(block_size))]
+    block: Option<usize>,
+    /// Prints nothing, the exit code tells whether the files are equal
+    #[clap(short, long)]
+    quiet: bool,
+}
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Compares raw bytes
+    Byte,
+    /// Compares UTF-8 characters
+    Char,
+    /// Compares single bits, the most significant bit of a byte first
+    Bit,
+}
+impl Mode {
+    fn unit(self) -> &'static str {
+        match self {
+            Mode::Byte => "bytes",
+            Mode::Char => "chars",
+            Mode::Bit => "bits",
+        }
+    }
+}
+/// Result of comparing two files unit by unit
+struct Diff {
+    /// Length of the first and the second file in units
+    lengths: (usize, usize),
+    /// Number of differing units among the ones both files have
+    mismatches: usize,
+    /// Offset and both values of the first differences
+    first: Vec<(usize, String, String)>,
+}
+impl Diff {
+    fn is_equal(&self) -> bool {
+        self.mismatches == 0 && self.lengths.0 == self.lengths.1
+    }
+}
+fn block_size(val: &str) -> Result<(), String> {
+    match val.parse::<usize>() {
+        Ok(n) if (1..=MAX_BLOCK).contains(&n) => Ok(()),
+        _ => Err(format!("block size needed to be in 1..={}", MAX_BLOCK)),
+    }
 }
 fn main() -> std::io::Result<()> {
     let opts = Opts::parse();
-    let mut one = File::open(opts.one_input)?;
-    let mut two = File::open(opts.two_input)?;
-    println!("First File size: {}", one.metadata().unwrap().len());
-    println!("Second File size: {}", two.metadata().unwrap().len());
-    let mut string_one = String::new();
-    one.read_to_string(&mut string_one)?;
-    let mut string_two = String::new();
-    two.read_to_string(&mut string_two)?;
-    let vc_1 = string_one.chars().collect::<Vec<_>>();
-    let vc_2 = string_two.chars().collect::<Vec<_>>();
-
-    let mut err: Vec<usize> = Vec::new();
-    for i in 0..vc_1.len() {
-        if i < vc_2.len() {
-            if vc_1[i] != vc_2[i] {
-                err.push(i);
+    let one = fs::read(&opts.one_input)?;
+    let two = fs::read(&opts.two_input)?;
+    if !report(&opts, &one, &two, &mut std::io::stdout().lock())? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+/// Compares the files as the options say and prints the report unless quiet,
+/// returns whether they are equal
+fn report<W: Write>(opts: &Opts, one: &[u8], two: &[u8], out: &mut W) -> std::io::Result<bool> {
+    let diff = match opts.mode {
+        Mode::Byte => compare(one, two, opts.first),
+        Mode::Char => compare(
+            &to_chars(one, &opts.one_input)?,
+            &to_chars(two, &opts.two_input)?,
+            opts.first,
+        ),
+        Mode::Bit => compare_bits(one, two, opts.first),
+    };
+    if !opts.quiet {
+        print_diff(out, opts, one.len(), two.len(), &diff)?;
+        if let Some(block) = opts.block {
+            print_blocks(out, one, two, block)?;
+        }
+    }
+    Ok(diff.is_equal())
+}
+fn print_diff<W: Write>(
+    out: &mut W,
+    opts: &Opts,
+    one_size: usize,
+    two_size: usize,
+    diff: &Diff,
+) -> std::io::Result<()> {
+    let unit = opts.mode.unit();
+    writeln!(out, "First File size: {}", one_size)?;
+    writeln!(out, "Second File size: {}", two_size)?;
+    if diff.is_equal() {
+        return writeln!(out, "All Equal");
+    }
+    let common = diff.lengths.0.min(diff.lengths.1);
+    writeln!(
+        out,
+        "{} of {} compared {} differ ({:.6}%)",
+        diff.mismatches,
+        common,
+        unit,
+        100.0 * diff.mismatches as f64 / common.max(1) as f64
+    )?;
+    match diff.lengths.0.cmp(&diff.lengths.1) {
+        std::cmp::Ordering::Less => writeln!(
+            out,
+            "Second file is {} {} longer",
+            diff.lengths.1 - diff.lengths.0,
+            unit
+        )?,
+        std::cmp::Ordering::Greater => writeln!(
+            out,
+            "First file is {} {} longer",
+            diff.lengths.0 - diff.lengths.1,
+            unit
+        )?,
+        std::cmp::Ordering::Equal => {}
+    }
+    if !diff.first.is_empty() {
+        writeln!(out, "First {} differences:", diff.first.len())?;
+        for (offset, one, two) in &diff.first {
+            writeln!(out, "Err index = {} || {} != {}", offset, one, two)?;
+        }
+    }
+    Ok(())
+}
+/// Prints the bit error rate and the histogram of flipped bits per block
+fn print_blocks<W: Write>(
+    out: &mut W,
+    one: &[u8],
+    two: &[u8],
+    block: usize,
+) -> std::io::Result<()> {
+    let histogram = block_histogram(one, two, block);
+    let flipped: usize = histogram
+        .iter()
+        .enumerate()
+        .map(|(errors, blocks)| errors * blocks)
+        .sum();
+    let compared = one.len().min(two.len()) * 8;
+    writeln!(
+        out,
+        "Bit error rate: {} of {} bits = {:e}",
+        flipped,
+        compared,
+        flipped as f64 / compared.max(1) as f64
+    )?;
+    writeln!(out, "Errors per {} byte block:", block)?;
+    for (errors, blocks) in histogram.iter().enumerate().filter(|(_, &n)| n > 0) {
+        writeln!(out, "{:>4}: {}", errors, blocks)?;
+    }
+    // a Hamming code corrects a single flipped bit per codeword
+    let beyond: usize = histogram.iter().skip(2).sum();
+    writeln!(out, "Blocks beyond single error correction: {}", beyond)
+}
+/// Counts the blocks of the common part of both files by their number of flipped bits,
+/// the last block may be shorter
+fn block_histogram(one: &[u8], two: &[u8], block: usize) -> Vec<usize> {
+    let common = one.len().min(two.len());
+    let mut histogram = vec![0; block * 8 + 1];
+    for (a, b) in one[..common].chunks(block).zip(two[..common].chunks(block)) {
+        let errors: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        histogram[errors as usize] += 1;
+    }
+    histogram
+}
+fn to_chars(bytes: &[u8], name: &str) -> std::io::Result<Vec<char>> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text.chars().collect()),
+        Err(err) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{:?} is not valid UTF-8 ({}), compare it with --mode byte",
+                name, err
+            ),
+        )),
+    }
+}
+/// Compares the units both files have and records the first `first` differences
+fn compare<T: PartialEq + Debug>(one: &[T], two: &[T], first: usize) -> Diff {
+    let mut diff = Diff {
+        lengths: (one.len(), two.len()),
+        mismatches: 0,
+        first: Vec::new(),
+    };
+    for (offset, (a, b)) in one.iter().zip(two).enumerate() {
+        if a != b {
+            diff.mismatches += 1;
+            if diff.first.len() < first {
+                diff.first
+                    .push((offset, format!("{:?}", a), format!("{:?}", b)));
             }
-        } else {
-            err.push(i);
         }
     }
-    if err.len() == 0 {
-        println!("All Equal");
-    } else {
-        /* for i in err {
-            if i < vc_2.len() {
-                println!("Err index = {} ||{:?}!={:?}", i, vc_1[i], vc_2[i]);
-            } else {
-                println!(
-                    "Err index = {} || {:?} doesn`t contains in second file",
-                    i, vc_1[i]
-                )
+    diff
+}
+/// Compares the files bit by bit, a byte at a time
+fn compare_bits(one: &[u8], two: &[u8], first: usize) -> Diff {
+    let mut diff = Diff {
+        lengths: (one.len() * 8, two.len() * 8),
+        mismatches: 0,
+        first: Vec::new(),
+    };
+    for (index, (a, b)) in one.iter().zip(two).enumerate() {
+        let flipped = a ^ b;
+        diff.mismatches += flipped.count_ones() as usize;
+        for bit in (0..8).filter(|bit| flipped & (0x80 >> bit) != 0) {
+            if diff.first.len() == first {
+                break;
             }
-        }*/
-        println!("{}", err.len() - 1);
+            diff.first.push((
+                index * 8 + bit,
+                ((a >> (7 - bit)) & 1).to_string(),
+                ((b >> (7 - bit)) & 1).to_string(),
+            ));
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_histogram, compare, compare_bits, report, to_chars, Mode, Opts, MAX_BLOCK};
+    use clap::Parser;
+
+    fn opts(args: &[&str]) -> Opts {
+        Opts::parse_from(["Comparator", "-o", "one", "-t", "two"].iter().chain(args))
+    }
+
+    fn run(args: &[&str], one: &[u8], two: &[u8]) -> (bool, String) {
+        let mut out = Vec::new();
+        let equal = report(&opts(args), one, two, &mut out).unwrap();
+        (equal, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn equal_inputs() {
+        for mode in ["byte", "char", "bit"] {
+            let (equal, out) = run(&["-m", mode], "zażółć".as_bytes(), "zażółć".as_bytes());
+            assert!(equal);
+            assert!(out.ends_with("All Equal\n"), "{}", out);
+        }
+        assert!(run(&[], b"", b"").0);
+    }
+
+    #[test]
+    fn inputs_of_different_lengths() {
+        let diff = compare(b"abcd", b"ab", 10);
+        assert_eq!((diff.lengths, diff.mismatches), ((4, 2), 0));
+        assert!(!diff.is_equal());
+        let (equal, out) = run(&["-m", "byte"], b"ab", b"abcd");
+        assert!(!equal);
+        assert!(out.contains("Second file is 2 bytes longer"), "{}", out);
+        let diff = compare_bits(b"a", b"ab", 10);
+        assert_eq!(diff.lengths, (8, 16));
+    }
+
+    #[test]
+    fn multi_byte_char_differs_once() {
+        // 'ł' and 'ń' are both two bytes, whose second bytes differ
+        let one = to_chars("załóg".as_bytes(), "one").unwrap();
+        let two = to_chars("zańóg".as_bytes(), "two").unwrap();
+        let diff = compare(&one, &two, 10);
+        assert_eq!((diff.lengths, diff.mismatches), ((5, 5), 1));
+        assert_eq!(diff.first, [(2, "'ł'".to_string(), "'ń'".to_string())]);
+        let diff = compare("załóg".as_bytes(), "zańóg".as_bytes(), 10);
+        assert_eq!(diff.lengths, (7, 7));
+        assert!(to_chars(&[0xc5], "cut").is_err());
+    }
+
+    #[test]
+    fn first_limits_the_listed_differences() {
+        let diff = compare(b"aaaaa", b"bbbbb", 2);
+        assert_eq!(diff.mismatches, 5);
+        assert_eq!(diff.first.len(), 2);
+        let diff = compare_bits(&[0xff], &[0x00], 3);
+        assert_eq!(diff.mismatches, 8);
+        let offsets: Vec<_> = diff.first.iter().map(|(offset, _, _)| *offset).collect();
+        assert_eq!(offsets, [0, 1, 2]);
+        let (_, out) = run(&["-m", "byte", "-f", "1"], b"xyz", b"abc");
+        assert!(out.contains("First 1 differences:"), "{}", out);
+        assert_eq!(out.matches("Err index").count(), 1);
+    }
+
+    #[test]
+    fn flipped_bits_per_block() {
+        let one = [0x00; 5];
+        let two = [0x01, 0x00, 0x03, 0xff, 0x80];
+        // blocks [01 00] [03 ff] [80] flip 1, 10 and 1 bits, the last one is shorter
+        let mut expected = vec![0; 17];
+        expected[1] = 2;
+        expected[10] = 1;
+        assert_eq!(block_histogram(&one, &two, 2), expected);
+        // only the common part is compared
+        let histogram = block_histogram(&one[..1], &two, 1);
+        assert_eq!(histogram.iter().sum::<usize>(), 1);
+        assert_eq!(histogram[1], 1);
+    }
+
+    #[test]
+    fn bit_error_rate() {
+        let one = [0x00; 4];
+        let (_, out) = run(&["-m", "bit", "-b", "2"], &one, &[0x01, 0x00, 0x03, 0x00]);
+        assert!(
+            out.contains("Bit error rate: 3 of 32 bits = 9.375e-2"),
+            "{}",
+            out
+        );
+        assert!(out.contains("   1: 1\n   2: 1\n"), "{}", out);
+        assert!(out.contains("beyond single error correction: 1"), "{}", out);
+        let (_, out) = run(&["-m", "bit", "-b", "1"], &one[..3], &[0x00, 0x10, 0x00]);
+        assert!(out.contains("   0: 2\n   1: 1\n"), "{}", out);
+        assert!(out.contains("beyond single error correction: 0"), "{}", out);
+    }
+
+    #[test]
+    fn block_size_is_bounded() {
+        let parse = |block: String| {
+            Opts::try_parse_from(["Comparator", "-o", "one", "-t", "two", "-b", &block])
+        };
+        assert!(parse(MAX_BLOCK.to_string()).is_ok());
+        assert!(parse((MAX_BLOCK + 1).to_string()).is_err());
+        assert!(parse("0".to_string()).is_err());
+        assert!(parse(usize::MAX.to_string()).is_err());
+    }
+
+    #[test]
+    fn quiet_prints_nothing() {
+        let (equal, out) = run(&["-q", "-b", "1"], b"abc", b"abd");
+        assert!(!equal);
+        assert!(out.is_empty());
+        assert!(run(&["--quiet"], b"abc", b"abc").0);
+        assert_eq!(opts(&[]).mode, Mode::Char);
     }
-    Ok(())
 }
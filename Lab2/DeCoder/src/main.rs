@@ -1,14 +1,18 @@
-use bitvec::{bits, bitvec, macros::internal::funty::IsNumber, order::Msb0, prelude::BitVec};
 use clap::Parser;
-use huff_tree::huff_tree::Tree;
-mod huff_tree;
+use compression::{
+    ans::{self, NormalizedTable},
+    arithmetic::{self, FrequencyTable},
+    block::BlockTree,
+    bwt,
+    container::{self, Codec, Header},
+    context::{self, ContextTrees},
+    dary::{self, DaryTree},
+    huff_tree, lz78, lzss, prefix_codes,
+    tokens::{self, TokenTrees},
+};
 use std::{
-    collections::HashMap,
-    convert::TryInto,
-    fs::File,
-    io::{BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write},
-    path::PathBuf,
-    vec,
+    fs::{self, File},
+    io::{BufWriter, Error, ErrorKind, Write},
 };
 #[derive(Parser)]
 #[clap(version = "1.0", author = "Aleksey S. <siroggi5@gmail.com>")]
@@ -19,146 +23,205 @@ struct Opts {
     /// Sets the name of the output file
     #[clap(short, long)]
     output: String,
+    /// Sets the shared tree the input was coded with, see the Coder's --save-tree
+    #[clap(short, long)]
+    tree: Option<String>,
 }
 fn main() -> std::io::Result<()> {
-    let opts = Opts::parse();
+    run(Opts::parse())
+}
+/// Decodes the input as asked by `opts` and writes the original bytes
+fn run(opts: Opts) -> std::io::Result<()> {
     // read from src file
-    let src = File::open(&opts.input)?;
-    let mut src_bytes_left = src.metadata().unwrap().len() as usize;
-    let reader = BufReader::new(src);
-
-    // write to dst file
-    let dst = File::create(opts.output)?;
-    let mut writer = BufWriter::new(dst);
+    let src = fs::read(&opts.input)?;
+    let shared = opts.tree.as_ref().map(fs::read).transpose()?;
+    let in_input = |err: Error| Error::new(err.kind(), format!("{:?} {}", opts.input, err));
 
-    // allocate a u8 buffer of size == block_size
-    let mut buf = vec![0; 1024];
-
-    // read only first 5 bytes
-    let mut reader = reader.take(5);
-    let bytes_read = reader.read(&mut buf)?;
-    if bytes_read < 5 {
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            format!(
-                "{:?} too short to decompress, missing header information",
-                &opts.input
-            ),
-        ));
-    }
-    src_bytes_left -= 5;
-
-    // read padding info from the first byte
-    let padding = buf[0];
-    let tree_padding_bits = padding >> 4;
-    let data_padding_bits = padding & 0b0000_1111;
-    if tree_padding_bits > 7 || data_padding_bits > 7 {
-        return Err(Error::new(
+    let (header, header_len) = Header::read(&src).map_err(in_input)?;
+    let model = header.resolve_model(shared.as_deref()).map_err(in_input)?;
+    let invalid_model = |_| {
+        Error::new(
             ErrorKind::InvalidData,
-            format!("{:?} stores invalid header information", opts.input),
-        ));
-    }
-    // read tree_bin's length
-    let tree_len = u32::from_be_bytes(buf[1..5].try_into().unwrap()) as usize;
+            format!("{:?} stores an invalid model", &opts.input),
+        )
+    };
 
-    // read only next tree_len bytes
-    reader.set_limit(tree_len as u64);
-    let bytes_read = reader.read(&mut buf)?;
-    if bytes_read < tree_len {
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            format!(
-                "{:?} too short to decompress, missing header information",
-                &opts.input
-            ),
-        ));
-    }
-    src_bytes_left -= tree_len;
+    // write to dst file
+    let dst = File::create(&opts.output)?;
+    let mut writer = BufWriter::new(dst);
 
-    // read the HuffTree
-    let tree = match Tree::try_from_bin({
-        let mut b = BitVec::from_vec(buf[..tree_len].to_vec());
-        for _ in 0..tree_padding_bits {
-            b.pop();
-        }
-        b
-    }) {
-        Ok(tree) => tree,
-        Err(_) => {
-            return Err(Error::new(
-                ErrorKind::NotFound,
+    // decompress the remaining bytes
+    let payload = &src[header_len..];
+    let bytes = match header.codec {
+        Codec::Huffman => {
+            // read the HuffTree
+            let tree = container::huffman_tree(model).map_err(invalid_model)?;
+            huff_tree::decompress(payload, header.padding_bits, &tree).into_bytes()
+        }
+        Codec::Arithmetic => {
+            let table = FrequencyTable::from_bytes(model).map_err(invalid_model)?;
+            arithmetic::decompress(payload, &table)?.into_bytes()
+        }
+        Codec::Rans => {
+            let table = NormalizedTable::from_bytes(model).map_err(invalid_model)?;
+            ans::rans_decompress(payload, &table)?.into_bytes()
+        }
+        Codec::Tans => {
+            let table = NormalizedTable::from_bytes(model).map_err(invalid_model)?;
+            ans::tans_decompress(payload, &table)?.into_bytes()
+        }
+        Codec::Lzss => {
+            let trees = lzss::Trees::from_bytes(model).map_err(invalid_model)?;
+            lzss::decompress(payload, &trees)?
+        }
+        Codec::Lz78 => {
+            let max_bits = container::dictionary_bits(model).map_err(invalid_model)?;
+            lz78::lz78_decompress(payload, max_bits)?
+        }
+        Codec::Lzw => {
+            let max_bits = container::dictionary_bits(model).map_err(invalid_model)?;
+            lz78::lzw_decompress(payload, max_bits)?
+        }
+        Codec::CodeTable => {
+            let codes = prefix_codes::from_bytes::<char>(model).map_err(invalid_model)?;
+            prefix_codes::decompress(payload, header.padding_bits, &codes)?
+                .into_iter()
+                .collect::<String>()
+                .into_bytes()
+        }
+        Codec::Dary => {
+            let tree = DaryTree::<char>::from_bytes(model).map_err(invalid_model)?;
+            dary::decompress(payload, &tree)?
+                .into_iter()
+                .collect::<String>()
+                .into_bytes()
+        }
+        Codec::Context => {
+            let trees = ContextTrees::from_bytes(model).map_err(invalid_model)?;
+            // only files from before the container lack the length, and they are Huffman coded
+            let original_len = header.original_len.unwrap_or(u32::MAX) as usize;
+            context::decompress(payload, &trees, original_len)?.into_bytes()
+        }
+        Codec::Tokens => {
+            let trees = TokenTrees::from_bytes(model).map_err(invalid_model)?;
+            tokens::decompress(payload, header.padding_bits, &trees)?.into_bytes()
+        }
+        Codec::Block => {
+            let block = BlockTree::from_bytes(model).map_err(invalid_model)?;
+            prefix_codes::decompress(payload, header.padding_bits, &block.tree.read_codes())?
+                .concat()
+                .into_bytes()
+        }
+        Codec::Bwt => {
+            let tree = container::symbol_tree(model).map_err(invalid_model)?;
+            bwt::decompress(payload, &tree)?
+        }
+    };
+    if let Some(original_len) = header.original_len {
+        if bytes.len() != original_len as usize {
+            return Err(in_input(Error::new(
+                ErrorKind::InvalidData,
                 format!(
-                    "{:?} too short to decompress, missing header information",
-                    &opts.input
+                    "decodes to {} bytes, the original had {}",
+                    bytes.len(),
+                    original_len
                 ),
-            ))
+            )));
         }
-    };
-    // decompress the remaining bytes
-    let mut reader = reader.into_inner();
-    decompress_to_writer(
-        &mut reader,
-        &mut writer,
-        &mut src_bytes_left,
-        &mut buf,
-        tree,
-        data_padding_bits,
-    )?;
+    }
+    writer.write_all(&bytes)?;
 
     writer.flush()?;
     Ok(())
 }
-fn decompress_to_writer<R: Read, W: Write>(
-    reader: &mut R,
-    writer: &mut W,
-    reader_bytes_left: &mut usize,
-    buf: &mut [u8],
-    tree: Tree,
-    padding_bits: u8,
-) -> Result<(), Error> {
-    // do pretty much the same thing as in huff_coding::comp::decompress
-    // see it's docs for an explanation
-    let mut decomp_buf = Vec::new();
-    let mut current_branch = tree.root();
-    macro_rules! read_codes_in_byte {
-        ($byte: expr;[$bitrange:expr]) => {
-            for bit_ptr in $bitrange {
-                if current_branch.has_children() {
-                    match ($byte >> (7 - bit_ptr)) & 1 == 1 {
-                        true => {
-                            current_branch = current_branch.right_child().unwrap();
-                        }
-                        false => {
-                            current_branch = current_branch.left_child().unwrap();
-                        }
-                    }
-                }
-                if !current_branch.has_children() {
-                    decomp_buf.push(current_branch.letter().unwrap().clone());
-                    current_branch = tree.root();
-                }
-            }
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compression::{container::Model, huff_encode::Tree};
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    /// A directory of its own for every test, in the temporary directory
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("decoder-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
     }
-    // try to read exactly buf.len() bytes, decompressing them and writing
-    while reader.read_exact(buf).is_ok() {
-        for byte in &buf[..] {
-            read_codes_in_byte!(byte;[0..8]);
-        }
-        let tmp = &decomp_buf.iter().collect::<String>();
-        writer.write_all(tmp.as_bytes())?;
-        decomp_buf.clear();
-        *reader_bytes_left -= buf.len();
+
+    fn decode(input: &Path, output: &Path, tree: Option<&Path>) -> std::io::Result<()> {
+        let mut args = vec![
+            "DeCoder".to_string(),
+            "-i".to_string(),
+            input.display().to_string(),
+            "-o".to_string(),
+            output.display().to_string(),
+        ];
+        if let Some(tree) = tree {
+            args.extend(["-t".to_string(), tree.display().to_string()]);
+        }
+        run(Opts::try_parse_from(args).unwrap())
     }
-    // if couldn't read exactly buf.len() bytes and there are some bytes left,
-    // decompress them minding the padding bits
-    if *reader_bytes_left > 0 {
-        for byte in &buf[..*reader_bytes_left - 1] {
-            read_codes_in_byte!(byte;[0..8]);
-        }
-        read_codes_in_byte!(buf[*reader_bytes_left - 1];[0..8 - padding_bits]);
-        let tmp = &decomp_buf.iter().collect::<String>();
-        writer.write_all(tmp.as_bytes())?;
+
+    /// Huffman model of `text` and the text coded with it, referring to the model by id
+    fn shared_huffman(text: &str) -> (Vec<u8>, Vec<u8>) {
+        let mut weights = HashMap::new();
+        for letter in text.chars() {
+            *weights.entry(letter).or_insert(0) += 1;
+        }
+        let mut tree = Tree::build_from_weights(weights);
+        tree.assign_codes();
+        let model = container::huffman_model(tree.as_bin());
+        let tree = container::huffman_tree(&model).unwrap();
+        let (payload, padding_bits, _) = huff_tree::compress_with_tree(text.to_string(), tree)
+            .unwrap()
+            .into_inner();
+        let mut bytes = Header {
+            codec: Codec::Huffman,
+            model: Model::Shared(container::model_id(&model)),
+            padding_bits,
+            original_len: Some(text.len() as u32),
+        }
+        .to_bytes();
+        bytes.extend(payload);
+        (model, bytes)
+    }
+
+    #[test]
+    fn decodes_with_the_shared_model() {
+        let dir = scratch("shared");
+        let (model, bytes) = shared_huffman("mississippi");
+        let (input, output, tree) = (
+            dir.join("input.bin"),
+            dir.join("output.txt"),
+            dir.join("tree.bin"),
+        );
+        fs::write(&input, bytes).unwrap();
+        fs::write(&tree, &model).unwrap();
+        decode(&input, &output, Some(&tree)).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "mississippi");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn wrong_or_missing_model_writes_nothing() {
+        let dir = scratch("mismatch");
+        let (_, bytes) = shared_huffman("mississippi");
+        let (other, _) = shared_huffman("banana");
+        let (input, output, tree) = (
+            dir.join("input.bin"),
+            dir.join("output.txt"),
+            dir.join("tree.bin"),
+        );
+        fs::write(&input, bytes).unwrap();
+        fs::write(&tree, other).unwrap();
+        let err = decode(&input, &output, Some(&tree)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("shared model"), "{}", err);
+        let err = decode(&input, &output, None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(!output.exists());
+        fs::remove_dir_all(dir).unwrap();
     }
-    Ok(())
 }
@@ -0,0 +1,144 @@
+//! Encoding and decoding speed of the entropy coders on the same messages.
+//!
+//! Run with `cargo bench -p compression`, the bytes per second are of the
+//! message before coding.
+use compression::{
+    ans::{self, NormalizedTable},
+    arithmetic::{self, FrequencyTable},
+    container,
+    huff_encode::{self, Tree},
+    huff_tree,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+const LENGTH: usize = 1 << 16;
+
+/// `LENGTH` letters drawn from `weights` by a xorshift generator, so every run codes the same text
+fn message(weights: &[(char, u32)]) -> String {
+    let total: u32 = weights.iter().map(|&(_, w)| w).sum();
+    let mut state = 0x2545_f491_u32;
+    (0..LENGTH)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let mut pick = state % total;
+            weights
+                .iter()
+                .find(|&&(_, w)| {
+                    let found = pick < w;
+                    pick = pick.saturating_sub(w);
+                    found
+                })
+                .unwrap()
+                .0
+        })
+        .collect()
+}
+
+fn messages() -> Vec<(&'static str, String)> {
+    let english = [
+        (' ', 180),
+        ('e', 102),
+        ('t', 75),
+        ('a', 65),
+        ('o', 62),
+        ('i', 57),
+        ('n', 57),
+        ('s', 53),
+        ('r', 50),
+        ('h', 49),
+        ('l', 33),
+        ('d', 33),
+        ('u', 23),
+        ('c', 22),
+        ('m', 20),
+        ('f', 18),
+        ('w', 17),
+        ('g', 16),
+        ('y', 15),
+        ('p', 15),
+        ('b', 12),
+        ('v', 8),
+        ('k', 6),
+        ('x', 2),
+        ('j', 1),
+        ('q', 1),
+        ('z', 1),
+    ];
+    vec![
+        ("english", message(&english)),
+        // a source far below 1 bit/symbol, where Huffman loses the most
+        ("skewed", message(&[('a', 97), ('b', 3)])),
+    ]
+}
+
+/// The model of every coder, built from the letters of `text`
+fn models(text: &str) -> (huff_tree::Tree, FrequencyTable, NormalizedTable) {
+    let weights = huff_encode::build_weights(text);
+    let mut tree = Tree::build_from_weights(weights.clone());
+    tree.assign_codes();
+    let tree = container::huffman_tree(&container::huffman_model(tree.as_bin())).unwrap();
+    (
+        tree,
+        FrequencyTable::new(weights.clone()),
+        NormalizedTable::new(weights).unwrap(),
+    )
+}
+
+fn encode(c: &mut Criterion) {
+    for (name, text) in messages() {
+        let (tree, frequencies, normalized) = models(&text);
+
+        let mut group = c.benchmark_group(format!("encode/{}", name));
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function("huffman", |b| {
+            b.iter_batched(
+                || (text.clone(), tree.clone()),
+                |(text, tree)| huff_tree::compress_with_tree(text, tree).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function("arithmetic", |b| {
+            b.iter(|| arithmetic::compress(&text, &frequencies).unwrap())
+        });
+        group.bench_function("rans", |b| {
+            b.iter(|| ans::rans_compress(&text, &normalized).unwrap())
+        });
+        group.bench_function("tans", |b| {
+            b.iter(|| ans::tans_compress(&text, &normalized).unwrap())
+        });
+        group.finish();
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    for (name, text) in messages() {
+        let (tree, frequencies, normalized) = models(&text);
+        let (huffman, padding_bits, _) = huff_tree::compress_with_tree(text.clone(), tree.clone())
+            .unwrap()
+            .into_inner();
+        let (arithmetic, _) = arithmetic::compress(&text, &frequencies).unwrap();
+        let rans = ans::rans_compress(&text, &normalized).unwrap();
+        let (tans, _) = ans::tans_compress(&text, &normalized).unwrap();
+
+        let mut group = c.benchmark_group(format!("decode/{}", name));
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function("huffman", |b| {
+            b.iter(|| huff_tree::decompress(&huffman, padding_bits, &tree))
+        });
+        group.bench_function("arithmetic", |b| {
+            b.iter(|| arithmetic::decompress(&arithmetic, &frequencies).unwrap())
+        });
+        group.bench_function("rans", |b| {
+            b.iter(|| ans::rans_decompress(&rans, &normalized).unwrap())
+        });
+        group.bench_function("tans", |b| {
+            b.iter(|| ans::tans_decompress(&tans, &normalized).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -0,0 +1,417 @@
+//! Asymmetric numeral systems: entropy coders about as close to the entropy
+//! as arithmetic coding at the cost of a few integer operations per symbol.
+//!
+//! The whole message is kept in one integer state, which every symbol of
+//! probability `p` grows by about `log2(1/p)` bits, the low bits being flushed
+//! to keep it in range. The decoder takes the state apart in reverse, so the
+//! symbols are encoded from the last one and the flushed bits are reversed.
+//!
+//! * rANS computes every step with a multiplication and a division of a 32-bit
+//!   state, flushing whole bytes.
+//! * tANS looks every step up in tables of `2^scale_bits` states built from
+//!   the frequencies, flushing single bits (Finite State Entropy).
+//!
+//! Both write the number of symbols as a big endian u32 before the data.
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind},
+};
+
+/// Frequencies are scaled to sum to `2^scale_bits`, at least this many bits
+pub const DEFAULT_SCALE_BITS: u8 = 12;
+/// Smallest table tANS spreads the symbols over evenly
+const MIN_SCALE_BITS: u8 = 5;
+/// Largest table, which also bounds the alphabet
+const MAX_SCALE_BITS: u8 = 16;
+/// Lower bound of the rANS state, which stays in `RANS_LOW..RANS_LOW << 8`
+const RANS_LOW: u32 = 1 << 23;
+
+/// Frequencies summing to a power of two, the model stored in the container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedTable {
+    scale_bits: u8,
+    /// Symbols in increasing order
+    symbols: Vec<char>,
+    frequencies: Vec<u32>,
+    /// `cumulative[i]` is the sum of the frequencies before symbol `i`
+    cumulative: Vec<u32>,
+}
+
+impl NormalizedTable {
+    /// Scales counts like the ones of [`build_weights`][crate::huff_encode::build_weights]
+    /// to sum to `2^scale_bits`, giving every symbol at least 1. The scale is
+    /// [`DEFAULT_SCALE_BITS`] or larger for alphabets that do not fit.
+    pub fn new(weights: HashMap<char, usize>) -> Result<Self, Error> {
+        let mut weights = weights
+            .into_iter()
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        weights.sort_unstable();
+        if weights.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no symbols to build a frequency table from".to_string(),
+            ));
+        }
+        let scale_bits = (DEFAULT_SCALE_BITS..=MAX_SCALE_BITS)
+            .find(|&bits| weights.len() <= 1 << (bits - 1))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "{} symbols are too many for a frequency table",
+                        weights.len()
+                    ),
+                )
+            })?;
+        let target = 1u64 << scale_bits;
+        let total = weights.iter().map(|&(_, count)| count as u64).sum::<u64>();
+        let mut frequencies = weights
+            .iter()
+            .map(|&(_, count)| ((count as u64 * target + total / 2) / total).max(1))
+            .collect::<Vec<_>>();
+        // rounding leaves the sum a little off, which the most frequent symbols make up for
+        let mut order = (0..frequencies.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(frequencies[i]));
+        let mut sum = frequencies.iter().sum::<u64>();
+        if sum < target {
+            frequencies[order[0]] += target - sum;
+        }
+        while sum > target {
+            for &i in &order {
+                if sum == target {
+                    break;
+                }
+                if frequencies[i] > 1 {
+                    frequencies[i] -= 1;
+                    sum -= 1;
+                }
+            }
+        }
+        Ok(Self::from_frequencies(
+            scale_bits,
+            weights
+                .into_iter()
+                .zip(frequencies)
+                .map(|((letter, _), frequency)| (letter, frequency as u32))
+                .collect(),
+        ))
+    }
+    fn from_frequencies(scale_bits: u8, frequencies: Vec<(char, u32)>) -> Self {
+        let mut cumulative = Vec::with_capacity(frequencies.len());
+        let mut total = 0;
+        for &(_, frequency) in &frequencies {
+            cumulative.push(total);
+            total += frequency;
+        }
+        NormalizedTable {
+            scale_bits,
+            symbols: frequencies.iter().map(|&(letter, _)| letter).collect(),
+            frequencies: frequencies.into_iter().map(|(_, f)| f).collect(),
+            cumulative,
+        }
+    }
+    pub fn scale_bits(&self) -> u8 {
+        self.scale_bits
+    }
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+    /// Probability the coder assigns to `letter`, 0 for letters it cannot code
+    pub fn probability(&self, letter: char) -> f64 {
+        match self.symbols.binary_search(&letter) {
+            Ok(i) => self.frequencies[i] as f64 / (1u64 << self.scale_bits) as f64,
+            Err(_) => 0.0,
+        }
+    }
+    /// The scale bits as a byte, the number of symbols as a u32, then every
+    /// symbol and its frequency as u32s, all big endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(5 + 8 * self.symbols.len());
+        bytes.push(self.scale_bits);
+        bytes.extend_from_slice(&(self.symbols.len() as u32).to_be_bytes());
+        for (&letter, &frequency) in self.symbols.iter().zip(&self.frequencies) {
+            bytes.extend_from_slice(&(letter as u32).to_be_bytes());
+            bytes.extend_from_slice(&frequency.to_be_bytes());
+        }
+        bytes
+    }
+    /// Reads the table written by [`to_bytes`][NormalizedTable::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                "invalid ANS frequency table".to_string(),
+            )
+        };
+        let scale_bits = *bytes.first().ok_or_else(invalid)?;
+        if !(MIN_SCALE_BITS..=MAX_SCALE_BITS).contains(&scale_bits) {
+            return Err(invalid());
+        }
+        let n = read_u32(bytes, 1).ok_or_else(invalid)? as usize;
+        if n == 0 || bytes.len() != 5 + 8 * n {
+            return Err(invalid());
+        }
+        let mut frequencies = Vec::with_capacity(n);
+        for i in 0..n {
+            let letter = char::from_u32(read_u32(bytes, 5 + 8 * i).unwrap()).ok_or_else(invalid)?;
+            let frequency = read_u32(bytes, 9 + 8 * i).unwrap();
+            if frequency == 0 || frequencies.last().is_some_and(|&(prev, _)| prev >= letter) {
+                return Err(invalid());
+            }
+            frequencies.push((letter, frequency));
+        }
+        if frequencies.iter().map(|&(_, f)| f as u64).sum::<u64>() != 1 << scale_bits {
+            return Err(invalid());
+        }
+        Ok(Self::from_frequencies(scale_bits, frequencies))
+    }
+    /// Indices of the symbols of `letters`
+    fn indices(&self, letters: &str) -> Result<Vec<usize>, Error> {
+        letters
+            .chars()
+            .map(|letter| {
+                self.symbols.binary_search(&letter).map_err(|_| {
+                    Error::new(
+                        ErrorKind::NotFound,
+                        format!("letter not found in frequency table {}", letter),
+                    )
+                })
+            })
+            .collect()
+    }
+    /// The symbol of every slot of the `2^scale_bits` the frequencies divide
+    fn slots(&self) -> Vec<u32> {
+        let mut slots = Vec::with_capacity(1 << self.scale_bits);
+        for (symbol, &frequency) in self.frequencies.iter().enumerate() {
+            slots.extend(std::iter::repeat_n(symbol as u32, frequency as usize));
+        }
+        slots
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(at..at + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_count(bytes: &[u8]) -> Result<usize, Error> {
+    read_u32(bytes, 0).map(|n| n as usize).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            "ANS coded data is missing its length".to_string(),
+        )
+    })
+}
+
+fn truncated() -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        "ANS coded data ends too early".to_string(),
+    )
+}
+
+/// Encodes `letters` with rANS, the data is a whole number of bytes
+pub fn rans_compress(letters: &str, table: &NormalizedTable) -> Result<Vec<u8>, Error> {
+    let symbols = table.indices(letters)?;
+    let scale_bits = table.scale_bits as u32;
+    // flushed bytes in reverse order
+    let mut flushed = Vec::with_capacity(symbols.len() / 2);
+    let mut state = RANS_LOW;
+    for &symbol in symbols.iter().rev() {
+        let frequency = table.frequencies[symbol];
+        let max_state = ((RANS_LOW >> scale_bits) << 8) * frequency;
+        while state >= max_state {
+            flushed.push(state as u8);
+            state >>= 8;
+        }
+        state = ((state / frequency) << scale_bits) + state % frequency + table.cumulative[symbol];
+    }
+    flushed.extend_from_slice(&state.to_le_bytes());
+    let mut bytes = (symbols.len() as u32).to_be_bytes().to_vec();
+    bytes.extend(flushed.into_iter().rev());
+    Ok(bytes)
+}
+
+/// Decodes the letters written by [`rans_compress`]
+pub fn rans_decompress(bytes: &[u8], table: &NormalizedTable) -> Result<String, Error> {
+    let count = read_count(bytes)?;
+    let scale_bits = table.scale_bits as u32;
+    let mask = (1 << scale_bits) - 1;
+    let slots = table.slots();
+    let mut state = read_u32(bytes, 4).ok_or_else(truncated)?;
+    let mut data = bytes[8..].iter();
+    let mut letters = String::with_capacity(count);
+    for _ in 0..count {
+        let slot = state & mask;
+        let symbol = slots[slot as usize] as usize;
+        letters.push(table.symbols[symbol]);
+        state = table.frequencies[symbol] * (state >> scale_bits) + slot - table.cumulative[symbol];
+        while state < RANS_LOW {
+            state = (state << 8) | *data.next().ok_or_else(truncated)? as u32;
+        }
+    }
+    Ok(letters)
+}
+
+/// Spreads the symbols over the states of tANS, each getting as many
+/// states as its frequency, scattered to keep the table from clustering
+fn spread(table: &NormalizedTable) -> Vec<u32> {
+    let size = 1 << table.scale_bits;
+    let step = (size >> 1) + (size >> 3) + 3;
+    let mut spread = vec![0; size];
+    let mut position = 0;
+    for (symbol, &frequency) in table.frequencies.iter().enumerate() {
+        for _ in 0..frequency {
+            spread[position] = symbol as u32;
+            position = (position + step) & (size - 1);
+        }
+    }
+    spread
+}
+
+/// Encodes `letters` with tANS, returning the bytes and the number of
+/// padding bits in the last one
+pub fn tans_compress(letters: &str, table: &NormalizedTable) -> Result<(Vec<u8>, u8), Error> {
+    let symbols = table.indices(letters)?;
+    let scale_bits = table.scale_bits as u32;
+    let size = 1 << scale_bits;
+    // the state that follows `x` in `frequency..2 * frequency` for every symbol,
+    // kept at `cumulative + x - frequency`
+    let mut next = vec![0; size as usize];
+    let mut seen = table.frequencies.clone();
+    for (position, &symbol) in spread(table).iter().enumerate() {
+        let symbol = symbol as usize;
+        let x = seen[symbol];
+        seen[symbol] += 1;
+        next[(table.cumulative[symbol] + x - table.frequencies[symbol]) as usize] =
+            size + position as u32;
+    }
+    // flushed bits and their count, in reverse order
+    let mut flushed = Vec::with_capacity(symbols.len());
+    let mut state = size;
+    for &symbol in symbols.iter().rev() {
+        let frequency = table.frequencies[symbol];
+        // `state >> bits` has to land in `frequency..2 * frequency`
+        let most = scale_bits - frequency.ilog2();
+        let bits = if most > 0 && state >= (2 * frequency) << (most - 1) {
+            most
+        } else {
+            most.saturating_sub(1)
+        };
+        flushed.push((state & ((1 << bits) - 1), bits));
+        state = next[(table.cumulative[symbol] + (state >> bits) - frequency) as usize];
+    }
+    let mut bytes = (symbols.len() as u32).to_be_bytes().to_vec();
+    // the low `filled` bits of `buffer` are yet to be written
+    let (mut buffer, mut filled) = (0u64, 0);
+    for (value, count) in
+        std::iter::once((state - size, scale_bits)).chain(flushed.into_iter().rev())
+    {
+        buffer = (buffer << count) | value as u64;
+        filled += count;
+        while filled >= 8 {
+            filled -= 8;
+            bytes.push((buffer >> filled) as u8);
+        }
+    }
+    let padding_bits = crate::huff_encode::calc_padding_bits(filled as usize);
+    if filled > 0 {
+        bytes.push((buffer << padding_bits) as u8);
+    }
+    Ok((bytes, padding_bits))
+}
+
+/// Decodes the letters written by [`tans_compress`]
+pub fn tans_decompress(bytes: &[u8], table: &NormalizedTable) -> Result<String, Error> {
+    let count = read_count(bytes)?;
+    let scale_bits = table.scale_bits as u32;
+    let size = 1 << scale_bits;
+    // symbol, number of bits to read and the state they are added to, for every state
+    let mut states = Vec::with_capacity(size as usize);
+    let mut seen = table.frequencies.clone();
+    for symbol in spread(table) {
+        let x = seen[symbol as usize];
+        seen[symbol as usize] += 1;
+        let bits = scale_bits - x.ilog2();
+        states.push((symbol, bits, (x << bits) - size));
+    }
+    let mut data = bytes[4..].iter();
+    // the low `filled` bits of `buffer` are yet to be read
+    let (mut buffer, mut filled) = (0u64, 0);
+    let mut read = |count: u32| -> Result<u32, Error> {
+        while filled < count {
+            buffer = (buffer << 8) | *data.next().ok_or_else(truncated)? as u64;
+            filled += 8;
+        }
+        filled -= count;
+        Ok(((buffer >> filled) & ((1 << count) - 1)) as u32)
+    };
+    let mut state = read(scale_bits)?;
+    let mut letters = String::with_capacity(count);
+    for _ in 0..count {
+        let (symbol, bits, base) = states[state as usize];
+        letters.push(table.symbols[symbol as usize]);
+        state = base + read(bits)?;
+    }
+    Ok(letters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rans_compress, rans_decompress, tans_compress, tans_decompress, NormalizedTable};
+    use crate::huff_encode::build_weights;
+
+    const TEXTS: [&str; 4] = ["a", "abracadabra", "ünïcödé and ascii \n", ""];
+
+    #[test]
+    fn frequencies_are_normalized() {
+        let table = NormalizedTable::new(build_weights(&"a".repeat(100_000))).unwrap();
+        assert_eq!(table.frequencies, vec![1 << table.scale_bits()]);
+        let weights = (0..5000)
+            .map(|i| (char::from_u32(0x4e00 + i).unwrap(), 1))
+            .collect();
+        let table = NormalizedTable::new(weights).unwrap();
+        assert_eq!(
+            table.frequencies.iter().sum::<u32>(),
+            1 << table.scale_bits()
+        );
+        assert_eq!(
+            NormalizedTable::from_bytes(&table.to_bytes()).unwrap(),
+            table
+        );
+    }
+
+    #[test]
+    fn rans_roundtrip() {
+        for text in TEXTS {
+            let table = NormalizedTable::new(build_weights(&format!("{}x", text))).unwrap();
+            let bytes = rans_compress(text, &table).unwrap();
+            assert_eq!(rans_decompress(&bytes, &table).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn tans_roundtrip() {
+        for text in TEXTS {
+            let table = NormalizedTable::new(build_weights(&format!("{}x", text))).unwrap();
+            let (bytes, padding_bits) = tans_compress(text, &table).unwrap();
+            assert!(padding_bits < 8);
+            assert_eq!(tans_decompress(&bytes, &table).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn skewed_source_takes_less_than_a_bit_per_symbol() {
+        let text = "a".repeat(990) + &"b".repeat(10);
+        let table = NormalizedTable::new(build_weights(&text)).unwrap();
+        let rans = rans_compress(&text, &table).unwrap();
+        let (tans, _) = tans_compress(&text, &table).unwrap();
+        // H = 0.0808 bits/symbol, Huffman needs 1
+        assert!(rans.len() * 8 < 150 && tans.len() * 8 < 150);
+        assert_eq!(rans_decompress(&rans, &table).unwrap(), text);
+        assert_eq!(tans_decompress(&tans, &table).unwrap(), text);
+    }
+}
@@ -0,0 +1,281 @@
+//! Static arithmetic coding with a 32-bit integer range.
+//!
+//! Every symbol narrows `[low, high]` in proportion to its frequency and the
+//! settled leading bits are shifted out (Witten, Neal and Cleary). An extra
+//! end symbol of frequency 1 closes the message, so the decoder needs nothing
+//! but the [`FrequencyTable`] and the payload.
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind},
+};
+
+const PRECISION: usize = 32;
+const WHOLE: u64 = 1 << PRECISION;
+const HALF: u64 = WHOLE / 2;
+const QUARTER: u64 = WHOLE / 4;
+/// Largest total frequency, which keeps the interval of every symbol non-empty
+const MAX_TOTAL: u64 = QUARTER;
+
+/// Frequencies the coder divides the range by, the model stored in the container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequencyTable {
+    /// Symbols in increasing order
+    symbols: Vec<char>,
+    /// `cumulative[i]..cumulative[i + 1]` is the share of symbol `i`, the last
+    /// share is the end symbol's
+    cumulative: Vec<u64>,
+}
+
+impl FrequencyTable {
+    /// Builds the table from counts like the ones of
+    /// [`build_weights`][crate::huff_encode::build_weights], scaling them
+    /// down when their total is too large
+    pub fn new(weights: HashMap<char, usize>) -> Self {
+        let mut weights = weights
+            .into_iter()
+            .filter(|&(_, count)| count > 0)
+            .map(|(letter, count)| (letter, count as u64))
+            .collect::<Vec<_>>();
+        weights.sort_unstable();
+        let mut shift = 0;
+        while weights
+            .iter()
+            .map(|&(_, count)| (count >> shift).max(1))
+            .sum::<u64>()
+            >= MAX_TOTAL
+        {
+            shift += 1;
+        }
+        Self::from_frequencies(
+            weights
+                .into_iter()
+                .map(|(letter, count)| (letter, (count >> shift).max(1)))
+                .collect(),
+        )
+    }
+    fn from_frequencies(frequencies: Vec<(char, u64)>) -> Self {
+        let mut cumulative = Vec::with_capacity(frequencies.len() + 2);
+        cumulative.push(0);
+        let mut total = 0;
+        // the end symbol comes last with a frequency of 1
+        for frequency in frequencies.iter().map(|&(_, f)| f).chain([1]) {
+            total += frequency;
+            cumulative.push(total);
+        }
+        FrequencyTable {
+            symbols: frequencies.into_iter().map(|(letter, _)| letter).collect(),
+            cumulative,
+        }
+    }
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+    /// Probability the coder assigns to `letter`, 0 for letters it cannot code
+    pub fn probability(&self, letter: char) -> f64 {
+        match self.symbols.binary_search(&letter) {
+            Ok(i) => (self.cumulative[i + 1] - self.cumulative[i]) as f64 / self.total() as f64,
+            Err(_) => 0.0,
+        }
+    }
+    fn total(&self) -> u64 {
+        *self.cumulative.last().unwrap()
+    }
+    fn end(&self) -> usize {
+        self.symbols.len()
+    }
+    /// The number of symbols as a u32, then every symbol and its frequency as u32s, all big endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + 8 * self.symbols.len());
+        bytes.extend_from_slice(&(self.symbols.len() as u32).to_be_bytes());
+        for (i, &letter) in self.symbols.iter().enumerate() {
+            let frequency = self.cumulative[i + 1] - self.cumulative[i];
+            bytes.extend_from_slice(&(letter as u32).to_be_bytes());
+            bytes.extend_from_slice(&(frequency as u32).to_be_bytes());
+        }
+        bytes
+    }
+    /// Reads the table written by [`to_bytes`][FrequencyTable::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                "invalid arithmetic coder frequency table".to_string(),
+            )
+        };
+        let read_u32 = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        };
+        let n = read_u32(0).ok_or_else(invalid)? as usize;
+        if bytes.len() != 4 + 8 * n {
+            return Err(invalid());
+        }
+        let mut frequencies = Vec::with_capacity(n);
+        for i in 0..n {
+            let letter = char::from_u32(read_u32(4 + 8 * i).unwrap()).ok_or_else(invalid)?;
+            let frequency = read_u32(8 + 8 * i).unwrap() as u64;
+            if frequency == 0 || frequencies.last().is_some_and(|&(prev, _)| prev >= letter) {
+                return Err(invalid());
+            }
+            frequencies.push((letter, frequency));
+        }
+        if frequencies.iter().map(|&(_, f)| f).sum::<u64>() >= MAX_TOTAL {
+            return Err(invalid());
+        }
+        Ok(Self::from_frequencies(frequencies))
+    }
+    /// Narrows `[low, high]` to the share of `symbol`
+    fn narrow(&self, low: &mut u64, high: &mut u64, symbol: usize) {
+        let range = *high - *low + 1;
+        *high = *low + range * self.cumulative[symbol + 1] / self.total() - 1;
+        *low += range * self.cumulative[symbol] / self.total();
+    }
+}
+
+/// Encodes `letters` followed by the end symbol, returning the bytes and
+/// the number of padding bits in the last one
+pub fn compress(letters: &str, table: &FrequencyTable) -> Result<(Vec<u8>, u8), Error> {
+    /// Pushes `bit` followed by the opposite bits postponed while the range straddled the middle
+    fn emit(bits: &mut BitVec<u8, Msb0>, pending: &mut usize, bit: bool) {
+        bits.push(bit);
+        for _ in 0..*pending {
+            bits.push(!bit);
+        }
+        *pending = 0;
+    }
+    let mut bits = BitVec::<u8, Msb0>::new();
+    let mut pending = 0;
+    let (mut low, mut high) = (0, WHOLE - 1);
+    let mut symbols = Vec::with_capacity(letters.len() + 1);
+    for letter in letters.chars() {
+        symbols.push(table.symbols.binary_search(&letter).map_err(|_| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("letter not found in frequency table {}", letter),
+            )
+        })?);
+    }
+    symbols.push(table.end());
+    for symbol in symbols {
+        table.narrow(&mut low, &mut high, symbol);
+        loop {
+            if high < HALF {
+                emit(&mut bits, &mut pending, false);
+            } else if low >= HALF {
+                emit(&mut bits, &mut pending, true);
+                low -= HALF;
+                high -= HALF;
+            } else if low >= QUARTER && high < HALF + QUARTER {
+                pending += 1;
+                low -= QUARTER;
+                high -= QUARTER;
+            } else {
+                break;
+            }
+            low *= 2;
+            high = high * 2 + 1;
+        }
+    }
+    // two more bits single out a quarter that lies within [low, high]
+    pending += 1;
+    emit(&mut bits, &mut pending, low >= QUARTER);
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// Decodes the letters up to the end symbol
+pub fn decompress(bytes: &[u8], table: &FrequencyTable) -> Result<String, Error> {
+    // reading past the end yields zeros, which the encoder's last bits allow for
+    let mut bits = bytes
+        .view_bits::<Msb0>()
+        .iter()
+        .by_vals()
+        .chain(std::iter::repeat(false))
+        .take(bytes.len() * 8 + 2 * PRECISION);
+    let mut value = 0;
+    for _ in 0..PRECISION {
+        value = value * 2 + bits.next().unwrap() as u64;
+    }
+    let (mut low, mut high) = (0, WHOLE - 1);
+    let mut letters = String::new();
+    loop {
+        let range = high - low + 1;
+        let scaled = ((value - low + 1) * table.total() - 1) / range;
+        let symbol = table.cumulative.partition_point(|&c| c <= scaled) - 1;
+        if symbol == table.end() {
+            return Ok(letters);
+        }
+        letters.push(table.symbols[symbol]);
+        table.narrow(&mut low, &mut high, symbol);
+        loop {
+            let shift = if high < HALF {
+                0
+            } else if low >= HALF {
+                HALF
+            } else if low >= QUARTER && high < HALF + QUARTER {
+                QUARTER
+            } else {
+                break;
+            };
+            value -= shift;
+            low -= shift;
+            high -= shift;
+            low *= 2;
+            high = high * 2 + 1;
+            let bit = bits.next().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "arithmetic coded data has no end symbol".to_string(),
+                )
+            })?;
+            value = value * 2 + bit as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress, FrequencyTable};
+    use crate::huff_encode::build_weights;
+
+    #[test]
+    fn roundtrip() {
+        for text in ["", "a", "abracadabra", "ünïcödé and ascii \n"] {
+            let table = FrequencyTable::new(build_weights(text));
+            let (bytes, padding_bits) = compress(text, &table).unwrap();
+            assert!(padding_bits < 8);
+            assert_eq!(decompress(&bytes, &table).unwrap(), text);
+            assert_eq!(
+                FrequencyTable::from_bytes(&table.to_bytes()).unwrap(),
+                table
+            );
+        }
+    }
+
+    #[test]
+    fn skewed_source_takes_less_than_a_bit_per_symbol() {
+        let text = "a".repeat(990) + &"b".repeat(10);
+        let table = FrequencyTable::new(build_weights(&text));
+        let (bytes, _) = compress(&text, &table).unwrap();
+        // H = 0.0808 bits/symbol, Huffman needs 1
+        assert!(bytes.len() * 8 < 100);
+        assert_eq!(decompress(&bytes, &table).unwrap(), text);
+    }
+
+    #[test]
+    fn large_counts_are_scaled() {
+        let weights = [('a', usize::MAX / 4), ('b', 1)].into_iter().collect();
+        let table = FrequencyTable::new(weights);
+        let (bytes, _) = compress("abba", &table).unwrap();
+        assert_eq!(decompress(&bytes, &table).unwrap(), "abba");
+    }
+
+    #[test]
+    fn unknown_letter_is_an_error() {
+        let table = FrequencyTable::new(build_weights("ab"));
+        assert!(compress("abc", &table).is_err());
+    }
+}
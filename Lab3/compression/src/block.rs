@@ -0,0 +1,205 @@
+//! Block Huffman codes, over tuples of `k` consecutive letters taken as one.
+//!
+//! A Huffman code spends at least a bit on every letter, far above the entropy
+//! of a source as skewed as the Generator's binary output. The code of a
+//! `k`-tuple spreads that bit over `k` letters, so the average length per letter
+//! stays below H(A) + 1/k for a memoryless source. The tail of fewer than `k`
+//! letters is a tuple of its own.
+use crate::{huff_encode::Tree, information};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Longest tuple, the alphabet of tuples grows as the alphabet to the power `k`
+pub const MAX_TUPLE_LEN: usize = 16;
+
+/// The letters of `text` as tuples of `k`, the last one shorter when they do not divide evenly
+pub fn tuples(text: &str, k: usize) -> impl Iterator<Item = String> + '_ {
+    let k = k.max(1);
+    let mut chars = text.chars().peekable();
+    std::iter::from_fn(move || {
+        chars.peek()?;
+        Some(chars.by_ref().take(k).collect())
+    })
+}
+
+/// Counts of the tuples of `k` letters of `text`
+pub fn build_weights(text: &str, k: usize) -> HashMap<String, usize> {
+    let mut weights = HashMap::new();
+    for tuple in tuples(text, k) {
+        *weights.entry(tuple).or_insert(0) += 1;
+    }
+    weights
+}
+
+/// Entropy of the tuples of `k` letters of `text` spread over its letters,
+/// H_k/k, the fewest bits per letter any code of the tuples can average
+pub fn block_entropy(text: &str, k: usize) -> f64 {
+    let weights = build_weights(text, k);
+    let tuples: usize = weights.values().sum();
+    let letters = text.chars().count();
+    if letters == 0 {
+        return 0.0;
+    }
+    information::entropy_of_counts(weights.into_values()) * tuples as f64 / letters as f64
+}
+
+/// Huffman tree over the tuples of `k` letters
+#[derive(Debug, Clone)]
+pub struct BlockTree {
+    pub k: usize,
+    pub tree: Tree<String>,
+}
+
+impl BlockTree {
+    /// Builds the tree of the tuples of `text`, an empty text has no tuple
+    /// to build it from and is rejected
+    pub fn build(text: &str, k: usize) -> Result<Self, Error> {
+        let weights = build_weights(text, k);
+        if weights.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a block tree needs a tuple, the text is empty".to_string(),
+            ));
+        }
+        let mut tree = Tree::build_from_weights(weights);
+        tree.assign_codes();
+        Ok(BlockTree { k, tree })
+    }
+    /// Average bits per letter of `text` coded with the tree, which has to
+    /// know every tuple of it
+    pub fn bits_per_letter(&self, text: &str) -> f64 {
+        let codes = self.tree.read_codes();
+        let bits: usize = tuples(text, self.k).map(|tuple| codes[&tuple].len()).sum();
+        bits as f64 / text.chars().count().max(1) as f64
+    }
+    /// `k` as u8, a u32 count of tuples, every tuple as a u16 length and its
+    /// UTF-8 bytes, then the tree of their indices as in
+    /// [`huffman_model`][crate::container::huffman_model]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut tuples: Vec<String> = self.tree.read_codes().into_keys().collect();
+        tuples.sort();
+        let indices: HashMap<&String, u32> = tuples
+            .iter()
+            .enumerate()
+            .map(|(i, tuple)| (tuple, i as u32))
+            .collect();
+        let mut bytes = vec![self.k as u8];
+        bytes.extend_from_slice(&(tuples.len() as u32).to_be_bytes());
+        for tuple in &tuples {
+            bytes.extend_from_slice(&(tuple.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(tuple.as_bytes());
+        }
+        let tree = self.tree.map_letters(&mut |tuple| indices[tuple]);
+        bytes.extend(crate::container::huffman_model(tree.as_bin()));
+        bytes
+    }
+    /// Reads the tree written by [`to_bytes`][BlockTree::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid block tree".to_string());
+        let k = *bytes.first().ok_or_else(invalid)? as usize;
+        if !(1..=MAX_TUPLE_LEN).contains(&k) {
+            return Err(invalid());
+        }
+        let count = bytes
+            .get(1..5)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or_else(invalid)?;
+        let mut at = 5;
+        let mut tuples = Vec::new();
+        for _ in 0..count {
+            let len = bytes
+                .get(at..at + 2)
+                .map(|b| u16::from_be_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(invalid)?;
+            let tuple = bytes.get(at + 2..at + 2 + len).ok_or_else(invalid)?;
+            tuples.push(String::from_utf8(tuple.to_vec()).map_err(|_| invalid())?);
+            at += 2 + len;
+        }
+        let tree = crate::container::symbol_tree::<u32>(&bytes[at..])?;
+        if tree
+            .read_codes()
+            .keys()
+            .any(|&index| index as usize >= tuples.len())
+        {
+            return Err(invalid());
+        }
+        Ok(BlockTree {
+            k,
+            tree: tree.map_letters(&mut |&index| tuples[index as usize].clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix_codes;
+
+    #[test]
+    fn tail_is_a_shorter_tuple() {
+        assert_eq!(
+            tuples("abcdefg", 3).collect::<Vec<_>>(),
+            ["abc", "def", "g"]
+        );
+        assert_eq!(tuples("", 3).count(), 0);
+        assert_eq!(tuples("ąę", 1).collect::<Vec<_>>(), ["ą", "ę"]);
+    }
+
+    #[test]
+    fn roundtrip_through_bytes() {
+        let text = "0001000000100000000110000100";
+        for k in 1..=5 {
+            let block = BlockTree::build(text, k).unwrap();
+            let read = BlockTree::from_bytes(&block.to_bytes()).unwrap();
+            assert_eq!(read.k, k);
+            let codes = read.tree.read_codes();
+            let (bytes, padding_bits) = prefix_codes::compress(tuples(text, k), &codes).unwrap();
+            let decoded = prefix_codes::decompress(&bytes, padding_bits, &codes).unwrap();
+            assert_eq!(decoded.concat(), text);
+        }
+    }
+
+    #[test]
+    fn approaches_entropy() {
+        // 'a' 7 times in 8, about 0.54 bits/letter
+        let text = "baaaaaaa".repeat(512);
+        let per_letter = |k| BlockTree::build(&text, k).unwrap().bits_per_letter(&text);
+        assert_eq!(per_letter(1), 1.0);
+        assert!(per_letter(2) < 0.8);
+        assert!(per_letter(4) < per_letter(2));
+    }
+
+    #[test]
+    fn gap_to_block_entropy_shrinks() {
+        // memoryless, '1' with probability 1/8 drawn by a linear congruential generator
+        let mut state = 12345u32;
+        let text: String = (0..1 << 14)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if (state >> 16).is_multiple_of(8) {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect();
+        let gap = |k| {
+            BlockTree::build(&text, k).unwrap().bits_per_letter(&text) - block_entropy(&text, k)
+        };
+        for k in 1..=4 {
+            // Huffman spends less than a bit over the entropy of a tuple
+            assert!(gap(k) >= 0.0);
+            assert!(gap(k) < 1.0 / k as f64);
+        }
+        assert!(gap(2) < gap(1));
+        assert!(gap(4) < gap(2));
+    }
+
+    #[test]
+    fn empty_text_is_rejected() {
+        assert!(BlockTree::build("", 3).is_err());
+        assert_eq!(block_entropy("", 3), 0.0);
+    }
+}
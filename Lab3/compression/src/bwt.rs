@@ -0,0 +1,346 @@
+//! Burrows–Wheeler transform, move-to-front and zero-run RLE before Huffman codes, as in bzip2.
+//!
+//! Every block of the input is replaced by the last column of its sorted
+//! rotations, which groups the bytes that precede the same context. Move-to-front
+//! turns those groups into runs of small ranks, mostly zeros, and the runs of
+//! zeros are written as bijective base 2 numbers of [`RUN_A`] and [`RUN_B`]
+//! digits. The ranks left are shifted by one, so symbols fit in 0..=[`END`].
+use crate::huff_encode::Tree;
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Block size of bzip2 at its best
+pub const DEFAULT_BLOCK: usize = 900_000;
+pub const MAX_BLOCK: usize = 1 << 24;
+/// Digit 1 of the length of a run of zeros
+pub const RUN_A: u16 = 0;
+/// Digit 2 of the length of a run of zeros
+pub const RUN_B: u16 = 1;
+/// Symbol ending a block, rank `r` is symbol `r + 1`
+pub const END: u16 = 257;
+
+/// A transformed block, ready for Huffman codes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Row of the block itself among its sorted rotations
+    pub primary: u32,
+    pub symbols: Vec<u16>,
+}
+
+/// Start of every rotation of `block` in sorted order, by prefix doubling
+/// with counting sorts on the classes of the halves
+fn sort_rotations(block: &[u8]) -> Vec<usize> {
+    let n = block.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&i| block[i]);
+    let mut classes = vec![0; n];
+    for i in 1..n {
+        classes[order[i]] =
+            classes[order[i - 1]] + (block[order[i]] != block[order[i - 1]]) as usize;
+    }
+    let mut shifted = vec![0; n];
+    let mut counts = vec![0; n];
+    let mut next = vec![0; n];
+    let mut half = 1;
+    while half < n && classes[order[n - 1]] + 1 < n {
+        // sorted by the second half, then stably by the first
+        for (to, &from) in shifted.iter_mut().zip(&order) {
+            *to = (from + n - half) % n;
+        }
+        counts.iter_mut().for_each(|count| *count = 0);
+        for &i in &shifted {
+            counts[classes[i]] += 1;
+        }
+        for class in 1..n {
+            counts[class] += counts[class - 1];
+        }
+        for &i in shifted.iter().rev() {
+            counts[classes[i]] -= 1;
+            order[counts[classes[i]]] = i;
+        }
+        next[order[0]] = 0;
+        for i in 1..n {
+            let (current, previous) = (order[i], order[i - 1]);
+            let differs = classes[current] != classes[previous]
+                || classes[(current + half) % n] != classes[(previous + half) % n];
+            next[current] = next[previous] + differs as usize;
+        }
+        std::mem::swap(&mut classes, &mut next);
+        half *= 2;
+    }
+    order
+}
+
+/// Last column of the sorted rotations of `block` and the row of `block` itself
+fn forward(block: &[u8]) -> (Vec<u8>, u32) {
+    let n = block.len();
+    let order = sort_rotations(block);
+    let primary = order.iter().position(|&i| i == 0).unwrap_or(0);
+    let last = order.iter().map(|&i| block[(i + n - 1) % n]).collect();
+    (last, primary as u32)
+}
+
+/// The block whose rotations end with `last`, following each byte to the row it precedes
+fn inverse(last: &[u8], primary: u32) -> Result<Vec<u8>, Error> {
+    if last.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut row = primary as usize;
+    if row >= last.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("BWT row {} is past a block of {} bytes", row, last.len()),
+        ));
+    }
+    let mut starts = [0; 256];
+    for &byte in last {
+        starts[byte as usize] += 1;
+    }
+    let mut total = 0;
+    for start in starts.iter_mut() {
+        total += *start;
+        *start = total - *start;
+    }
+    let preceding: Vec<usize> = last
+        .iter()
+        .map(|&byte| {
+            starts[byte as usize] += 1;
+            starts[byte as usize] - 1
+        })
+        .collect();
+    let mut block = vec![0; last.len()];
+    for byte in block.iter_mut().rev() {
+        *byte = last[row];
+        row = preceding[row];
+    }
+    Ok(block)
+}
+
+fn move_to_front(bytes: &[u8]) -> Vec<u8> {
+    let mut list: Vec<u8> = (0..=255).collect();
+    bytes
+        .iter()
+        .map(|&byte| {
+            let rank = list.iter().position(|&b| b == byte).unwrap();
+            list[..=rank].rotate_right(1);
+            rank as u8
+        })
+        .collect()
+}
+
+fn move_to_front_inverse(ranks: &[u8]) -> Vec<u8> {
+    let mut list: Vec<u8> = (0..=255).collect();
+    ranks
+        .iter()
+        .map(|&rank| {
+            list[..=rank as usize].rotate_right(1);
+            list[0]
+        })
+        .collect()
+}
+
+fn push_run(symbols: &mut Vec<u16>, mut run: usize) {
+    while run > 0 {
+        if run % 2 == 1 {
+            symbols.push(RUN_A);
+            run = (run - 1) / 2;
+        } else {
+            symbols.push(RUN_B);
+            run = (run - 2) / 2;
+        }
+    }
+}
+
+/// Transforms `data` a block of at most `block_size` bytes at a time
+pub fn transform(data: &[u8], block_size: usize) -> Vec<Block> {
+    data.chunks(block_size.clamp(1, MAX_BLOCK))
+        .map(|block| {
+            let (last, primary) = forward(block);
+            let mut symbols = Vec::new();
+            let mut run = 0;
+            for rank in move_to_front(&last) {
+                if rank == 0 {
+                    run += 1;
+                    continue;
+                }
+                push_run(&mut symbols, run);
+                run = 0;
+                symbols.push(rank as u16 + 1);
+            }
+            push_run(&mut symbols, run);
+            Block { primary, symbols }
+        })
+        .collect()
+}
+
+/// Move-to-front ranks of a block from its symbols
+fn ranks(symbols: &[u16]) -> Result<Vec<u8>, Error> {
+    let too_long = || Error::new(ErrorKind::InvalidData, "BWT block is too long".to_string());
+    let mut ranks = Vec::new();
+    let mut run = 0;
+    let mut weight = 1;
+    for &symbol in symbols {
+        match symbol {
+            RUN_A | RUN_B => {
+                run += weight * (symbol as usize + 1);
+                weight *= 2;
+                if run > MAX_BLOCK {
+                    return Err(too_long());
+                }
+            }
+            _ => {
+                ranks.extend(std::iter::repeat_n(0, run));
+                (run, weight) = (0, 1);
+                ranks.push((symbol - 1) as u8);
+            }
+        }
+    }
+    ranks.extend(std::iter::repeat_n(0, run));
+    if ranks.len() > MAX_BLOCK {
+        return Err(too_long());
+    }
+    Ok(ranks)
+}
+
+/// Builds the tree of the symbols of every block and their ends
+pub fn build_tree(blocks: &[Block]) -> Tree<u16> {
+    let mut weights = HashMap::from([(END, blocks.len().max(1))]);
+    for symbol in blocks.iter().flat_map(|block| &block.symbols) {
+        *weights.entry(*symbol).or_insert(0) += 1;
+    }
+    let mut tree = Tree::build_from_weights(weights);
+    tree.assign_codes();
+    tree
+}
+
+/// Writes the number of blocks and their rows as u32, then the Huffman coded
+/// symbols of each block followed by [`END`], returning the bytes and the number
+/// of padding bits in the last one
+pub fn compress(blocks: &[Block], tree: &Tree<u16>) -> Result<(Vec<u8>, u8), Error> {
+    let codes = tree.read_codes();
+    let code = |symbol: u16| {
+        codes.get(&symbol).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("symbol not found in BWT tree {}", symbol),
+            )
+        })
+    };
+    let mut bytes = (blocks.len() as u32).to_be_bytes().to_vec();
+    for block in blocks {
+        bytes.extend_from_slice(&block.primary.to_be_bytes());
+    }
+    let mut bits = BitVec::<u8, Msb0>::new();
+    for block in blocks {
+        for &symbol in &block.symbols {
+            bits.extend_from_bitslice(code(symbol)?);
+        }
+        bits.extend_from_bitslice(code(END)?);
+    }
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    bytes.extend(bits.into_vec());
+    Ok((bytes, padding_bits))
+}
+
+/// Decodes the bytes written by [`compress`]
+pub fn decompress(bytes: &[u8], tree: &Tree<u16>) -> Result<Vec<u8>, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let read_u32 = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("BWT coded data ends before the block rows"))
+    };
+    let count = read_u32(0)? as usize;
+    let rows_end = count
+        .checked_mul(4)
+        .and_then(|len| len.checked_add(4))
+        .filter(|&end| end <= bytes.len())
+        .ok_or_else(|| invalid("BWT coded data ends before the block rows"))?;
+    let mut bits = bytes[rows_end..].view_bits::<Msb0>().iter().by_vals();
+    let mut data = Vec::new();
+    for i in 0..count {
+        let mut block = Block {
+            primary: read_u32(4 + 4 * i)?,
+            symbols: Vec::new(),
+        };
+        loop {
+            let mut node = tree.root();
+            if !node.has_children() {
+                bits.next();
+            }
+            while node.has_children() {
+                let bit = bits
+                    .next()
+                    .ok_or_else(|| invalid("BWT coded data ends without the end symbol"))?;
+                node = if bit {
+                    node.right_child().unwrap()
+                } else {
+                    node.left_child().unwrap()
+                };
+            }
+            match node.letter().unwrap() {
+                END => break,
+                symbol if symbol > END => return Err(invalid("invalid BWT symbol")),
+                symbol => block.symbols.push(symbol),
+            }
+            if block.symbols.len() > 2 * MAX_BLOCK {
+                return Err(invalid("BWT block is too long"));
+            }
+        }
+        let last = move_to_front_inverse(&ranks(&block.symbols)?);
+        data.extend(inverse(&last, block.primary)?);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: &[u8], block_size: usize) -> usize {
+        let blocks = transform(data, block_size);
+        let tree = build_tree(&blocks);
+        let (bytes, _) = compress(&blocks, &tree).unwrap();
+        assert_eq!(decompress(&bytes, &tree).unwrap(), data);
+        bytes.len()
+    }
+
+    #[test]
+    fn transform_of_banana() {
+        assert_eq!(forward(b"banana"), (b"nnbaaa".to_vec(), 3));
+        assert_eq!(inverse(b"nnbaaa", 3).unwrap(), b"banana");
+    }
+
+    #[test]
+    fn periodic_and_short_blocks() {
+        for data in [&b""[..], b"a", b"aaaaaaaa", b"abababab", b"abcabcabcab"] {
+            for block_size in [1, 3, 8, DEFAULT_BLOCK] {
+                roundtrip(data, block_size);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_runs_are_bijective_base_2() {
+        for run in 0..100 {
+            let mut symbols = Vec::new();
+            push_run(&mut symbols, run);
+            symbols.push(2);
+            let mut expected = vec![0; run];
+            expected.push(1);
+            assert_eq!(ranks(&symbols).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn text_beats_plain_huffman() {
+        let text = b"she sells sea shells by the sea shore, the shells she sells are sea shells. "
+            .repeat(200);
+        let size = roundtrip(&text, DEFAULT_BLOCK);
+        assert!(size * 20 < text.len());
+    }
+}
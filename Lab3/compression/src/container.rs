@@ -0,0 +1,325 @@
+//! Header of the files written by the Coder.
+//!
+//! ```text
+//! MAGIC | codec id | padding bits | u32 original length | model kind | u32 | model bytes
+//! ```
+//!
+//! The original length in bytes lets the decoder bound and check its output,
+//! a corrupt payload cannot make it write more than the original had.
+//! The model is what the decoder needs besides the payload, e.g. the Huffman
+//! tree. It is either embedded, with the `u32` holding its length, or shared
+//! between many files and kept in a file of its own, with the `u32` holding its
+//! [`model_id`]. A shared model leaves a header of only [`SHARED_HEADER_LEN`] bytes.
+//!
+//! Files from before the container start with the padding byte of the Huffman
+//! coder instead, whose both halves are at most 7 and so never equal `MAGIC`.
+use bitvec::prelude::*;
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind},
+};
+
+/// First byte of every container
+pub const MAGIC: u8 = 0xA5;
+/// Length of the header of a file referring to a shared model
+pub const SHARED_HEADER_LEN: usize = 12;
+
+/// How the payload is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Huffman codes, the model is a tree from [`huffman_model`]
+    Huffman,
+    /// Arithmetic coding, the model is a [`FrequencyTable`][crate::arithmetic::FrequencyTable]
+    Arithmetic,
+    /// rANS, the model is a [`NormalizedTable`][crate::ans::NormalizedTable]
+    Rans,
+    /// tANS, the model is a [`NormalizedTable`][crate::ans::NormalizedTable]
+    Tans,
+    /// LZSS with Huffman coded symbols, the model is the pair of [`Trees`][crate::lzss::Trees]
+    Lzss,
+    /// LZ78 pairs, the model is the dictionary size from [`dictionary_model`]
+    Lz78,
+    /// LZW codes, the model is the dictionary size from [`dictionary_model`]
+    Lzw,
+    /// BWT, move-to-front and zero-run RLE before Huffman codes, the model is a
+    /// [`symbol_tree`] of the [`bwt`][crate::bwt] symbols
+    Bwt,
+    /// A prefix code such as Shannon–Fano's, the model is its table from
+    /// [`prefix_codes::to_bytes`][crate::prefix_codes::to_bytes]
+    CodeTable,
+    /// Huffman codes of tuples of letters, the model is a [`BlockTree`][crate::block::BlockTree]
+    Block,
+    /// Order-1 Huffman codes, the model is the [`ContextTrees`][crate::context::ContextTrees]
+    Context,
+    /// D-ary Huffman codes, the model is a [`DaryTree`][crate::dary::DaryTree]
+    /// from [`to_bytes`][crate::dary::DaryTree::to_bytes]
+    Dary,
+    /// Huffman codes of words and other tokens, the model is the
+    /// [`TokenTrees`][crate::tokens::TokenTrees] with the dictionary
+    Tokens,
+}
+
+impl Codec {
+    pub fn id(self) -> u8 {
+        match self {
+            Codec::Huffman => 0,
+            Codec::Arithmetic => 1,
+            Codec::Rans => 2,
+            Codec::Tans => 3,
+            Codec::Lzss => 4,
+            Codec::Lz78 => 5,
+            Codec::Lzw => 6,
+            Codec::Bwt => 7,
+            Codec::CodeTable => 8,
+            Codec::Block => 9,
+            Codec::Context => 10,
+            Codec::Dary => 11,
+            Codec::Tokens => 12,
+        }
+    }
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Codec::Huffman),
+            1 => Some(Codec::Arithmetic),
+            2 => Some(Codec::Rans),
+            3 => Some(Codec::Tans),
+            4 => Some(Codec::Lzss),
+            5 => Some(Codec::Lz78),
+            6 => Some(Codec::Lzw),
+            7 => Some(Codec::Bwt),
+            8 => Some(Codec::CodeTable),
+            9 => Some(Codec::Block),
+            10 => Some(Codec::Context),
+            11 => Some(Codec::Dary),
+            12 => Some(Codec::Tokens),
+            _ => None,
+        }
+    }
+}
+
+/// Where the decoder finds the model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Model {
+    /// Stored in the header itself
+    Embedded(Vec<u8>),
+    /// Kept in a separate file, identified by its [`model_id`]
+    Shared(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub codec: Codec,
+    pub model: Model,
+    /// Bits of the last payload byte that carry no data
+    pub padding_bits: u8,
+    /// Bytes of the original, `None` for files from before the container,
+    /// written as `u32::MAX`
+    pub original_len: Option<u32>,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![MAGIC, self.codec.id(), self.padding_bits];
+        bytes.extend_from_slice(&self.original_len.unwrap_or(u32::MAX).to_be_bytes());
+        match &self.model {
+            Model::Embedded(model) => {
+                bytes.push(0);
+                bytes.extend_from_slice(&(model.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(model);
+            }
+            Model::Shared(id) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&id.to_be_bytes());
+            }
+        }
+        bytes
+    }
+    /// Reads the header at the start of `bytes`, returning it together with
+    /// its length, i.e. the offset of the payload
+    pub fn read(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+        let too_short = || invalid("too short to decompress, missing header information");
+        let first = *bytes.first().ok_or_else(too_short)?;
+        if first != MAGIC {
+            // the Huffman coder's own header: padding, u32 tree length, tree
+            let tree_padding_bits = first >> 4;
+            let padding_bits = first & 0b0000_1111;
+            if tree_padding_bits > 7 || padding_bits > 7 {
+                return Err(invalid("stores invalid header information"));
+            }
+            let tree_len = read_u32(bytes, 1).ok_or_else(too_short)? as usize;
+            let tree = bytes.get(5..5 + tree_len).ok_or_else(too_short)?;
+            let mut model = vec![tree_padding_bits];
+            model.extend_from_slice(tree);
+            let header = Header {
+                codec: Codec::Huffman,
+                model: Model::Embedded(model),
+                padding_bits,
+                original_len: None,
+            };
+            return Ok((header, 5 + tree_len));
+        }
+        if bytes.len() < SHARED_HEADER_LEN {
+            return Err(too_short());
+        }
+        let codec = Codec::from_id(bytes[1])
+            .ok_or_else(|| invalid(&format!("unknown codec id {}", bytes[1])))?;
+        let padding_bits = bytes[2];
+        if padding_bits > 7 {
+            return Err(invalid("stores invalid header information"));
+        }
+        let original_len = read_u32(bytes, 3)
+            .ok_or_else(too_short)
+            .map(|len| (len != u32::MAX).then_some(len))?;
+        let value = read_u32(bytes, 8).ok_or_else(too_short)?;
+        let (model, len) = match bytes[7] {
+            0 => {
+                let end = SHARED_HEADER_LEN + value as usize;
+                let model = bytes.get(SHARED_HEADER_LEN..end).ok_or_else(too_short)?;
+                (Model::Embedded(model.to_vec()), end)
+            }
+            1 => (Model::Shared(value), SHARED_HEADER_LEN),
+            kind => return Err(invalid(&format!("unknown model kind {}", kind))),
+        };
+        let header = Header {
+            codec,
+            model,
+            padding_bits,
+            original_len,
+        };
+        Ok((header, len))
+    }
+    /// The model bytes, taken from `shared` when they are not embedded
+    pub fn resolve_model<'a>(&'a self, shared: Option<&'a [u8]>) -> Result<&'a [u8], Error> {
+        match (&self.model, shared) {
+            (Model::Embedded(model), _) => Ok(model),
+            (Model::Shared(id), Some(model)) if model_id(model) == *id => Ok(model),
+            (Model::Shared(id), Some(model)) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "encoded with the shared model {:08x}, not with the given {:08x}",
+                    id,
+                    model_id(model)
+                ),
+            )),
+            (Model::Shared(id), None) => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "encoded with the shared model {:08x}, which was not given",
+                    id
+                ),
+            )),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(at..at + 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Identifier of a shared model, the 32-bit FNV-1a hash of its bytes
+pub fn model_id(model: &[u8]) -> u32 {
+    model.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Model bytes of a Huffman tree: the number of padding bits followed by
+/// the tree as written by [`as_bin`][crate::huff_encode::Tree::as_bin]
+pub fn huffman_model(tree_bin: BitVec<u8, Msb0>) -> Vec<u8> {
+    let mut model = vec![crate::huff_encode::calc_padding_bits(tree_bin.len())];
+    model.extend(tree_bin.into_vec());
+    model
+}
+
+/// Reads the tree back from the bytes of [`huffman_model`]
+pub fn huffman_tree(model: &[u8]) -> Result<crate::huff_tree::Tree, Error> {
+    let (&padding_bits, tree) = model
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Huffman model is empty".to_string()))?;
+    let mut bin = BitVec::from_vec(tree.to_vec());
+    for _ in 0..padding_bits {
+        bin.pop();
+    }
+    crate::huff_tree::Tree::try_from_bin(bin)
+}
+
+/// Reads a tree of integer symbols, such as `u16`, back from the bytes of [`huffman_model`]
+pub fn symbol_tree<L: crate::huff_encode::HuffLetterAsBytes>(
+    model: &[u8],
+) -> Result<crate::huff_encode::Tree<L>, Error> {
+    let (&padding_bits, tree) = model
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Huffman model is empty".to_string()))?;
+    let mut bin = BitVec::<u8, Msb0>::from_vec(tree.to_vec());
+    bin.truncate(bin.len().saturating_sub(padding_bits as usize));
+    crate::huff_encode::Tree::try_from_bin(bin)
+}
+
+/// Model bytes of LZ78 and LZW, which only need the bits of the largest index
+pub fn dictionary_model(max_bits: u8) -> Vec<u8> {
+    vec![max_bits]
+}
+
+/// Reads the bits of the largest index back from the bytes of [`dictionary_model`]
+pub fn dictionary_bits(model: &[u8]) -> Result<u8, Error> {
+    match model {
+        [max_bits] if (crate::lz78::MIN_BITS..=crate::lz78::MAX_BITS).contains(max_bits) => {
+            Ok(*max_bits)
+        }
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            "dictionary model is not a size of the dictionary".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{model_id, Codec, Header, Model, SHARED_HEADER_LEN};
+
+    #[test]
+    fn header_roundtrip() {
+        for (codec, model) in [
+            (Codec::Huffman, Model::Embedded(vec![3, 1, 2])),
+            (Codec::Arithmetic, Model::Shared(0xdead_beef)),
+        ] {
+            let header = Header {
+                codec,
+                model,
+                padding_bits: 5,
+                original_len: Some(1234),
+            };
+            let mut bytes = header.to_bytes();
+            let len = bytes.len();
+            bytes.push(0xff);
+            assert_eq!(Header::read(&bytes).unwrap(), (header, len));
+        }
+    }
+
+    #[test]
+    fn shared_header_is_short() {
+        let header = Header {
+            codec: Codec::Huffman,
+            model: Model::Shared(model_id(b"tree")),
+            padding_bits: 0,
+            original_len: None,
+        };
+        assert_eq!(header.to_bytes().len(), SHARED_HEADER_LEN);
+        assert!(header.resolve_model(None).is_err());
+        assert!(header.resolve_model(Some(b"other")).is_err());
+        assert_eq!(header.resolve_model(Some(b"tree")).unwrap(), b"tree");
+    }
+
+    #[test]
+    fn legacy_header_is_read() {
+        let bytes = [0x32, 0, 0, 0, 2, 0xaa, 0xbb, 0xcc];
+        let (header, len) = Header::read(&bytes).unwrap();
+        assert_eq!(len, 7);
+        assert_eq!(header.padding_bits, 2);
+        assert_eq!(header.original_len, None);
+        assert_eq!(header.model, Model::Embedded(vec![3, 0xaa, 0xbb]));
+    }
+}
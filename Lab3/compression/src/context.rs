@@ -0,0 +1,344 @@
+//! Order-1 context Huffman codes, with a tree for every letter coding the letters that follow it.
+//!
+//! A first-order Markov source, like the Generator's, has a conditional entropy
+//! H(A|A^) below H(A), which no code of single letters gets under. Coding every
+//! letter with the tree of the letter before it spends H(A|A^) bits plus the
+//! Huffman loss of each context, and nothing at all after a letter that is
+//! always followed by the same one.
+use crate::huff_encode::Tree;
+use crate::prefix_codes::Codes;
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Most letters the trees may hold, they are written as u16 indices
+pub const MAX_ALPHABET: usize = 1 << 16;
+/// Bits of a letter index in the written trees
+const INDEX_BITS: usize = 16;
+
+/// Counts of the pairs of a letter and the letter before it
+pub fn build_weights(text: &str) -> HashMap<(char, char), usize> {
+    let mut weights = HashMap::new();
+    for pair in text.chars().zip(text.chars().skip(1)) {
+        *weights.entry(pair).or_insert(0) += 1;
+    }
+    weights
+}
+
+/// Huffman trees of the letters following every letter
+#[derive(Debug, Clone)]
+pub struct ContextTrees {
+    pub trees: HashMap<char, Tree>,
+}
+
+impl ContextTrees {
+    /// Builds a tree for every letter before another in `bigrams`, which maps
+    /// a letter and the letter after it to their count
+    pub fn build(bigrams: &HashMap<(char, char), usize>) -> Result<Self, Error> {
+        let mut successors: HashMap<char, HashMap<char, usize>> = HashMap::new();
+        for (&(before, letter), &count) in bigrams {
+            if count > 0 {
+                *successors
+                    .entry(before)
+                    .or_default()
+                    .entry(letter)
+                    .or_insert(0) += count;
+            }
+        }
+        let trees = ContextTrees {
+            trees: successors
+                .into_iter()
+                .map(|(before, weights)| {
+                    let mut tree = Tree::build_from_weights(weights);
+                    tree.assign_codes();
+                    (before, tree)
+                })
+                .collect(),
+        };
+        if trees.alphabet().len() > MAX_ALPHABET {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("more than {} letters for context trees", MAX_ALPHABET),
+            ));
+        }
+        Ok(trees)
+    }
+    /// Every letter of the trees, as context or as leaf, in order
+    fn alphabet(&self) -> Vec<char> {
+        let mut letters: Vec<char> = self
+            .trees
+            .iter()
+            .flat_map(|(&before, tree)| {
+                std::iter::once(before).chain(tree.read_codes().into_keys())
+            })
+            .collect();
+        letters.sort_unstable();
+        letters.dedup();
+        letters
+    }
+    /// The codes of the letters after every letter, empty ones after a letter
+    /// that has a single follower
+    pub fn codes(&self) -> HashMap<char, Codes> {
+        self.trees
+            .iter()
+            .map(|(&before, tree)| {
+                let mut codes = tree.read_codes();
+                if !tree.root().has_children() {
+                    codes.values_mut().for_each(|code| code.clear());
+                }
+                (before, codes)
+            })
+            .collect()
+    }
+    /// The alphabet as a u32 count and u32 letters, the contexts as a u32
+    /// count and u16 indices into it, then their trees as in
+    /// [`as_bin`][Tree::as_bin] with u16 indices for letters, one after another
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let alphabet = self.alphabet();
+        let indices: HashMap<char, u16> = alphabet
+            .iter()
+            .enumerate()
+            .map(|(i, &letter)| (letter, i as u16))
+            .collect();
+        let mut bytes = (alphabet.len() as u32).to_be_bytes().to_vec();
+        for &letter in &alphabet {
+            bytes.extend_from_slice(&(letter as u32).to_be_bytes());
+        }
+        let mut contexts: Vec<char> = self.trees.keys().copied().collect();
+        contexts.sort_unstable();
+        bytes.extend_from_slice(&(contexts.len() as u32).to_be_bytes());
+        let mut bits = BitVec::<u8, Msb0>::new();
+        for before in contexts {
+            bytes.extend_from_slice(&indices[&before].to_be_bytes());
+            let tree = self.trees[&before].map_letters(&mut |letter| indices[letter]);
+            bits.extend_from_bitslice(&tree.as_bin());
+        }
+        bytes.extend(bits.into_vec());
+        bytes
+    }
+    /// Reads the trees written by [`to_bytes`][ContextTrees::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid context trees".to_string());
+        let read_u32 = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(invalid)
+        };
+        let count = read_u32(0)?;
+        if count > MAX_ALPHABET {
+            return Err(invalid());
+        }
+        let alphabet = (0..count)
+            .map(|i| char::from_u32(read_u32(4 + 4 * i)? as u32).ok_or_else(invalid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut at = 4 + 4 * count;
+        let contexts = read_u32(at)?;
+        at += 4;
+        let letter = |at: usize| {
+            bytes
+                .get(at..at + 2)
+                .and_then(|b| alphabet.get(u16::from_be_bytes(b.try_into().unwrap()) as usize))
+                .copied()
+                .ok_or_else(invalid)
+        };
+        let befores = (0..contexts)
+            .map(|i| letter(at + 2 * i))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bits = bytes
+            .get(at + 2 * contexts..)
+            .ok_or_else(invalid)?
+            .view_bits::<Msb0>();
+        let mut at = 0;
+        let mut trees = HashMap::new();
+        for before in befores {
+            let len = tree_len(&bits[at..]).ok_or_else(invalid)?;
+            let tree = Tree::<u16>::try_from_bin(bits[at..at + len].to_bitvec())?;
+            if tree
+                .read_codes()
+                .keys()
+                .any(|&index| index as usize >= alphabet.len())
+            {
+                return Err(invalid());
+            }
+            trees.insert(
+                before,
+                tree.map_letters(&mut |&index| alphabet[index as usize]),
+            );
+            at += len;
+        }
+        if bits.len() - at >= 8 {
+            return Err(invalid());
+        }
+        Ok(ContextTrees { trees })
+    }
+    /// Bits spent on `letter` after `before`, `None` when the trees cannot code it
+    pub fn code_length(&self, before: char, letter: char) -> Option<usize> {
+        let tree = self.trees.get(&before)?;
+        let length = tree.read_codes().get(&letter)?.len();
+        Some(if tree.root().has_children() {
+            length
+        } else {
+            0
+        })
+    }
+}
+
+/// Bits of the tree at the start of `bits`, every leaf holding an index
+fn tree_len(bits: &BitSlice<u8, Msb0>) -> Option<usize> {
+    let mut at = 0;
+    let mut unread = 1;
+    while unread > 0 {
+        let joint = *bits.get(at)?;
+        at += 1;
+        unread -= 1;
+        if joint {
+            unread += 2;
+        } else {
+            at += INDEX_BITS;
+        }
+    }
+    (at <= bits.len()).then_some(at)
+}
+
+/// Writes the number of letters and the first letter as u32, then the codes of
+/// the others after their context, returning the bytes and the number of padding
+/// bits in the last one
+pub fn compress(text: &str, trees: &ContextTrees) -> Result<(Vec<u8>, u8), Error> {
+    let codes = trees.codes();
+    let mut bytes = (text.chars().count() as u32).to_be_bytes().to_vec();
+    let mut letters = text.chars();
+    let mut before = match letters.next() {
+        Some(first) => first,
+        None => return Ok((bytes, 0)),
+    };
+    bytes.extend_from_slice(&(before as u32).to_be_bytes());
+    let mut bits = BitVec::<u8, Msb0>::new();
+    for letter in letters {
+        let code = codes
+            .get(&before)
+            .and_then(|codes| codes.get(&letter))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("letter not found in codes {:?} after {:?}", letter, before),
+                )
+            })?;
+        bits.extend_from_bitslice(code);
+        before = letter;
+    }
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    bytes.extend(bits.into_vec());
+    Ok((bytes, padding_bits))
+}
+
+/// Decodes the bytes written by [`compress`] of a text of `original_len` bytes.
+///
+/// A letter whose context has a single follower takes no bits, so the data
+/// alone cannot tell a corrupt count of letters from a long run of them. A
+/// letter takes at least a byte of the original, which bounds the count.
+pub fn decompress(
+    bytes: &[u8],
+    trees: &ContextTrees,
+    original_len: usize,
+) -> Result<String, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let read_u32 = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("context coded data is too short"))
+    };
+    let count = read_u32(0)? as usize;
+    if count > original_len {
+        return Err(invalid(
+            "context coded data holds more letters than the original had bytes",
+        ));
+    }
+    let mut text = String::new();
+    if count == 0 {
+        return Ok(text);
+    }
+    let mut before = char::from_u32(read_u32(4)?).ok_or_else(|| invalid("invalid first letter"))?;
+    text.push(before);
+    let mut bits = bytes[8..].view_bits::<Msb0>().iter().by_vals();
+    for _ in 1..count {
+        let mut node = trees
+            .trees
+            .get(&before)
+            .ok_or_else(|| invalid("no context tree for a letter of the data"))?
+            .root();
+        while node.has_children() {
+            let bit = bits
+                .next()
+                .ok_or_else(|| invalid("context coded data ends too early"))?;
+            node = if bit {
+                node.right_child().unwrap()
+            } else {
+                node.left_child().unwrap()
+            };
+        }
+        before = node.letter().unwrap();
+        text.push(before);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for text in ["", "a", "abababab", "the cat sat on the mat, ąę"] {
+            let trees = ContextTrees::build(&build_weights(text)).unwrap();
+            let trees = ContextTrees::from_bytes(&trees.to_bytes()).unwrap();
+            let (bytes, _) = compress(text, &trees).unwrap();
+            assert_eq!(decompress(&bytes, &trees, text.len()).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn deterministic_successors_cost_nothing() {
+        let text = "abc".repeat(100);
+        let trees = ContextTrees::build(&build_weights(&text)).unwrap();
+        let (bytes, padding_bits) = compress(&text, &trees).unwrap();
+        assert_eq!((bytes.len(), padding_bits), (8, 0));
+        assert_eq!(trees.code_length('a', 'b'), Some(0));
+    }
+
+    #[test]
+    fn corrupt_count_is_bounded_by_the_original() {
+        let text = "abc".repeat(100);
+        let trees = ContextTrees::build(&build_weights(&text)).unwrap();
+        let (mut bytes, _) = compress(&text, &trees).unwrap();
+        // every letter after the first is determined, only the count says when to stop
+        bytes[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let err = decompress(&bytes, &trees, text.len()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        bytes[..4].copy_from_slice(&(text.len() as u32).to_be_bytes());
+        assert_eq!(decompress(&bytes, &trees, text.len()).unwrap(), text);
+    }
+
+    #[test]
+    fn beats_order_0_on_a_markov_source() {
+        // four letters, each followed by one of the next two
+        let mut state = 0x2545_f491_u32;
+        let mut letter = 0;
+        let text: String = (0..2000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                letter = (letter + 1 + state % 2) % 4;
+                (b'a' + letter as u8) as char
+            })
+            .collect();
+        let trees = ContextTrees::build(&build_weights(&text)).unwrap();
+        let (bytes, _) = compress(&text, &trees).unwrap();
+        // a bit per letter, where order 0 needs two
+        assert!(bytes.len() * 8 < text.len() + 100);
+    }
+}
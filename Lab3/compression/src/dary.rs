@@ -0,0 +1,413 @@
+//! D-ary Huffman codes, whose digits go to a channel of `D` levels such as a ternary or 4-level modulation.
+//!
+//! Every step merges the `D` lightest nodes. That fills the tree only when
+//! `(n - 1) % (D - 1) == 0` for `n` leaves, so the alphabet is first padded
+//! with dummy leaves of weight 0, which end up as the unused longest codes.
+//!
+//! The code stream is written as whole bytes of as many digits as fit, e.g.
+//! 5 ternary digits in a byte, as 3^5 = 243 <= 256.
+use crate::huff_encode::{HuffLetter, HuffLetterAsBytes};
+use bitvec::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    io::{Error, ErrorKind},
+};
+
+/// Most levels, a digit is a u8
+pub const MAX_ARITY: usize = 255;
+
+/// Huffman tree over letters of type `L` where every joint has `arity` children
+#[derive(Debug, Clone)]
+pub struct DaryTree<L: HuffLetter = char> {
+    arity: usize,
+    root: DaryNode<L>,
+}
+
+/// A letter, a joint with children, or a dummy leaf with neither
+#[derive(Debug, Clone)]
+pub struct DaryNode<L: HuffLetter = char> {
+    letter: Option<L>,
+    weight: usize,
+    children: Vec<DaryNode<L>>,
+}
+
+impl<L: HuffLetter> DaryNode<L> {
+    pub fn letter(&self) -> Option<L> {
+        self.letter.clone()
+    }
+    pub fn children(&self) -> &[DaryNode<L>] {
+        &self.children
+    }
+    pub fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+}
+
+fn check_arity(arity: usize) -> Result<(), Error> {
+    if (2..=MAX_ARITY).contains(&arity) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("arity {} is not in 2..={}", arity, MAX_ARITY),
+        ))
+    }
+}
+
+impl<L: HuffLetter> DaryTree<L> {
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+    pub fn root(&self) -> &DaryNode<L> {
+        &self.root
+    }
+    /// Builds the tree of `weights`, padding them with dummy leaves of weight 0
+    pub fn build_from_weights(weights: HashMap<L, usize>, arity: usize) -> Result<Self, Error> {
+        check_arity(arity)?;
+        if weights.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a D-ary tree needs a letter".to_string(),
+            ));
+        }
+        let mut nodes: Vec<DaryNode<L>> = weights
+            .into_iter()
+            .map(|(letter, weight)| DaryNode {
+                letter: Some(letter),
+                weight,
+                children: Vec::new(),
+            })
+            .collect();
+        while !(nodes.len() - 1).is_multiple_of(arity - 1) {
+            nodes.push(DaryNode {
+                letter: None,
+                weight: 0,
+                children: Vec::new(),
+            });
+        }
+        // the lightest node on top, the nodes stay in `nodes` and the heap holds their indices
+        let mut heap: BinaryHeap<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| Reverse((node.weight, index)))
+            .collect();
+        let mut nodes: Vec<Option<DaryNode<L>>> = nodes.into_iter().map(Some).collect();
+        while heap.len() > 1 {
+            let mut children = (0..arity)
+                .map(|_| {
+                    let Reverse((_, index)) = heap.pop().unwrap();
+                    nodes[index].take().unwrap()
+                })
+                .collect::<Vec<_>>();
+            // the heaviest child gets digit 0, the dummies the last ones
+            children.reverse();
+            let weight = children.iter().map(|child| child.weight).sum();
+            heap.push(Reverse((weight, nodes.len())));
+            nodes.push(Some(DaryNode {
+                letter: None,
+                weight,
+                children,
+            }));
+        }
+        let Reverse((_, root)) = heap.pop().unwrap();
+        Ok(DaryTree {
+            arity,
+            root: nodes[root].take().unwrap(),
+        })
+    }
+    /// The digits of every letter's code, a single 0 for a tree of one letter
+    pub fn read_codes(&self) -> HashMap<L, Vec<u8>> {
+        fn set_codes<L: HuffLetter>(
+            codes: &mut HashMap<L, Vec<u8>>,
+            node: &DaryNode<L>,
+            code: &mut Vec<u8>,
+        ) {
+            if let Some(letter) = node.letter() {
+                codes.insert(letter, code.clone());
+            }
+            for (digit, child) in node.children.iter().enumerate() {
+                code.push(digit as u8);
+                set_codes(codes, child, code);
+                code.pop();
+            }
+        }
+        let mut codes = HashMap::new();
+        let mut code = Vec::new();
+        if !self.root.has_children() {
+            code.push(0);
+        }
+        set_codes(&mut codes, &self.root, &mut code);
+        codes
+    }
+}
+
+impl<L: HuffLetterAsBytes> DaryTree<L> {
+    /// The tree in preorder: 1 for a joint followed by its children, 01 for a
+    /// letter followed by its bytes, 00 for a dummy leaf
+    pub fn as_bin(&self) -> BitVec<u8, Msb0> {
+        fn set_node_as_bin<L: HuffLetterAsBytes>(bin: &mut BitVec<u8, Msb0>, node: &DaryNode<L>) {
+            if node.has_children() {
+                bin.push(true);
+                for child in &node.children {
+                    set_node_as_bin(bin, child);
+                }
+            } else if let Some(letter) = node.letter() {
+                bin.extend_from_bitslice(bits![u8, Msb0; 0, 1]);
+                bin.extend_from_bitslice(letter.as_be_bytes().view_bits::<Msb0>());
+            } else {
+                bin.extend_from_bitslice(bits![u8, Msb0; 0, 0]);
+            }
+        }
+        let mut bin = BitVec::new();
+        set_node_as_bin(&mut bin, &self.root);
+        bin
+    }
+    /// Reads the tree written by [`as_bin`][DaryTree::as_bin], every letter taking `size_of::<L>()` bytes
+    pub fn try_from_bin(bin: &BitSlice<u8, Msb0>, arity: usize) -> Result<Self, Error> {
+        fn invalid(reason: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, reason.to_string())
+        }
+        fn read_node<L: HuffLetterAsBytes>(
+            bits: &mut bitvec::slice::Iter<u8, Msb0>,
+            arity: usize,
+            depth: usize,
+        ) -> Result<DaryNode<L>, Error> {
+            let too_short = || invalid("Provided bits are too short for an encoded D-ary tree");
+            let mut node = DaryNode {
+                letter: None,
+                weight: 0,
+                children: Vec::new(),
+            };
+            if *bits.next().ok_or_else(too_short)? {
+                // deeper than any code of a u32 count of letters
+                if depth > 64 {
+                    return Err(invalid("encoded D-ary tree is too deep"));
+                }
+                for _ in 0..arity {
+                    node.children.push(read_node(bits, arity, depth + 1)?);
+                }
+            } else if *bits.next().ok_or_else(too_short)? {
+                let letter_bits = 8 * std::mem::size_of::<L>();
+                let letter_bits = bits
+                    .as_bitslice()
+                    .get(..letter_bits)
+                    .ok_or_else(too_short)?;
+                let bytes: Vec<u8> = letter_bits.chunks(8).map(|byte| byte.load_be()).collect();
+                node.letter = Some(L::try_from_be_bytes(&bytes).map_err(|err| {
+                    invalid(&format!("Invalid letter in an encoded D-ary tree: {}", err))
+                })?);
+                for _ in 0..letter_bits.len() {
+                    bits.next();
+                }
+            }
+            Ok(node)
+        }
+        check_arity(arity)?;
+        let mut bits = bin.iter();
+        let root = read_node(&mut bits, arity, 0)?;
+        if bits.next().is_some() {
+            return Err(invalid(
+                "Provided bits are too long for an encoded D-ary tree",
+            ));
+        }
+        Ok(DaryTree { arity, root })
+    }
+    /// The arity as u8, then the tree as in [`huffman_model`][crate::container::huffman_model]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.arity as u8];
+        bytes.extend(crate::container::huffman_model(self.as_bin()));
+        bytes
+    }
+    /// Reads the tree written by [`to_bytes`][DaryTree::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid D-ary tree".to_string());
+        let (&arity, rest) = bytes.split_first().ok_or_else(invalid)?;
+        let (&padding_bits, tree) = rest.split_first().ok_or_else(invalid)?;
+        let bits = tree.view_bits::<Msb0>();
+        let len = bits
+            .len()
+            .checked_sub(padding_bits as usize)
+            .ok_or_else(invalid)?;
+        Self::try_from_bin(&bits[..len], arity as usize)
+    }
+}
+
+/// Most digits of `arity` levels a byte holds, `arity` has to pass [`check_arity`]
+fn digits_per_byte(arity: usize) -> usize {
+    let mut digits = 1;
+    let mut levels = arity;
+    while levels * arity <= 256 {
+        levels *= arity;
+        digits += 1;
+    }
+    digits
+}
+
+/// The number of digits as u32, then the digits packed as base-`arity`
+/// numbers of [`digits_per_byte`] digits each, the last one padded with zeros
+pub fn pack_digits(digits: &[u8], arity: usize) -> Result<Vec<u8>, Error> {
+    check_arity(arity)?;
+    if let Some(digit) = digits.iter().find(|&&digit| digit as usize >= arity) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("digit {} is out of range of arity {}", digit, arity),
+        ));
+    }
+    let per_byte = digits_per_byte(arity);
+    let mut bytes = (digits.len() as u32).to_be_bytes().to_vec();
+    for group in digits.chunks(per_byte) {
+        let mut byte = 0;
+        for i in 0..per_byte {
+            byte = byte * arity + group.get(i).copied().unwrap_or(0) as usize;
+        }
+        bytes.push(byte as u8);
+    }
+    Ok(bytes)
+}
+
+/// Reads the digits written by [`pack_digits`]
+pub fn unpack_digits(bytes: &[u8], arity: usize) -> Result<Vec<u8>, Error> {
+    check_arity(arity)?;
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let count = bytes
+        .get(..4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid("D-ary coded data is too short"))?;
+    let per_byte = digits_per_byte(arity);
+    if bytes.len() - 4 != count.div_ceil(per_byte) {
+        return Err(invalid(
+            "D-ary coded data does not hold its count of digits",
+        ));
+    }
+    let mut digits = Vec::with_capacity(count + per_byte);
+    let mut group = vec![0; per_byte];
+    for &byte in &bytes[4..] {
+        let mut value = byte as usize;
+        for digit in group.iter_mut().rev() {
+            *digit = (value % arity) as u8;
+            value /= arity;
+        }
+        if value != 0 {
+            return Err(invalid("D-ary coded byte is out of range"));
+        }
+        digits.extend_from_slice(&group);
+    }
+    digits.truncate(count);
+    Ok(digits)
+}
+
+/// Codes `letters` with the tree and packs the digits with [`pack_digits`]
+pub fn compress<L: HuffLetter, I: IntoIterator<Item = L>>(
+    letters: I,
+    tree: &DaryTree<L>,
+) -> Result<Vec<u8>, Error> {
+    let codes = tree.read_codes();
+    let mut digits = Vec::new();
+    for letter in letters {
+        let code = codes.get(&letter).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("letter not found in codes {:?}", letter),
+            )
+        })?;
+        digits.extend_from_slice(code);
+    }
+    pack_digits(&digits, tree.arity)
+}
+
+/// Decodes the bytes written by [`compress`]
+pub fn decompress<L: HuffLetter>(bytes: &[u8], tree: &DaryTree<L>) -> Result<Vec<L>, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let mut letters = Vec::new();
+    let mut node = &tree.root;
+    for digit in unpack_digits(bytes, tree.arity)? {
+        if node.has_children() {
+            node = &node.children[digit as usize];
+        }
+        if !node.has_children() {
+            letters.push(
+                node.letter()
+                    .ok_or_else(|| invalid("D-ary code of a dummy leaf"))?,
+            );
+            node = &tree.root;
+        }
+    }
+    if !std::ptr::eq(node, &tree.root) {
+        return Err(invalid("D-ary coded data ends inside a code"));
+    }
+    Ok(letters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_with_dummy_leaves() {
+        // 4 letters need a dummy for a ternary tree, (5 - 1) % 2 == 0
+        let weights = HashMap::from([('a', 5), ('b', 3), ('c', 2), ('d', 1)]);
+        let codes = DaryTree::build_from_weights(weights, 3)
+            .unwrap()
+            .read_codes();
+        let mut lengths: Vec<_> = "abcd".chars().map(|letter| codes[&letter].len()).collect();
+        lengths.sort_unstable();
+        assert_eq!(lengths, [1, 1, 2, 2]);
+        assert!(codes.values().flatten().all(|&digit| digit < 3));
+    }
+
+    #[test]
+    fn binary_matches_huffman() {
+        let weights = crate::huff_encode::build_weights("abracadabra alakazam");
+        let mut tree = crate::huff_encode::Tree::build_from_weights(weights.clone());
+        tree.assign_codes();
+        let binary = DaryTree::build_from_weights(weights.clone(), 2)
+            .unwrap()
+            .read_codes();
+        let total = |lengths: &dyn Fn(char) -> usize| -> usize {
+            weights
+                .iter()
+                .map(|(&letter, &n)| n * lengths(letter))
+                .sum()
+        };
+        let huffman = tree.read_codes();
+        assert_eq!(
+            total(&|letter| binary[&letter].len()),
+            total(&|letter| huffman[&letter].len())
+        );
+    }
+
+    #[test]
+    fn digits_pack_into_bytes() {
+        assert_eq!(digits_per_byte(2), 8);
+        assert_eq!(digits_per_byte(3), 5);
+        assert_eq!(digits_per_byte(4), 4);
+        assert_eq!(digits_per_byte(17), 1);
+        let digits = [2, 0, 1, 1, 2, 2, 0];
+        let bytes = pack_digits(&digits, 3).unwrap();
+        assert_eq!(bytes.len(), 4 + 2);
+        assert_eq!(unpack_digits(&bytes, 3).unwrap(), digits);
+        // arities below 2 would never fill a byte
+        assert!(pack_digits(&digits, 0).is_err());
+        assert!(pack_digits(&digits, 1).is_err());
+        // a binary digit is 0 or 1, 2 is out of its range
+        assert!(pack_digits(&digits, 2).is_err());
+        assert!(pack_digits(&[0, 1, 1], 2).is_ok());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = "she sells sea shells by the sea shore";
+        for arity in [2, 3, 4, 7, 40] {
+            let tree = DaryTree::build_from_weights(crate::huff_encode::build_weights(text), arity)
+                .unwrap();
+            let tree = DaryTree::<char>::from_bytes(&tree.to_bytes()).unwrap();
+            let bytes = compress(text.chars(), &tree).unwrap();
+            let letters = decompress(&bytes, &tree).unwrap();
+            assert_eq!(letters.into_iter().collect::<String>(), text);
+        }
+        let single = DaryTree::build_from_weights(HashMap::from([('x', 2)]), 3).unwrap();
+        let bytes = compress("xxx".chars(), &single).unwrap();
+        assert_eq!(decompress(&bytes, &single).unwrap(), ['x'; 3]);
+    }
+}
@@ -2,7 +2,7 @@ const INDEXES: [usize; 7] = [0, 1, 3, 7, 15, 31, 63];
 use bitvec::{prelude as bv, view::BitView};
 pub fn encode(mut block: [u8; 7]) -> Vec<u8> {
     let mut next = bv::BitVec::<u8, bv::Msb0>::with_capacity(64);
-    let mut bv = block.view_bits_mut::<bv::Msb0>();
+    let bv = block.view_bits_mut::<bv::Msb0>();
     //println!("{:?}", bv);
     let mut prev = 0;
     for index in 0..64 {
@@ -19,17 +19,17 @@ pub fn encode(mut block: [u8; 7]) -> Vec<u8> {
     }
     //println!("{}", next);
     let mut r = [0i32; INDEXES.len()];
-    for j in 0..INDEXES.len() {
-        for i in 0..next.len() {
+    for (j, parity) in r.iter_mut().enumerate() {
+        for (i, value) in values.iter().enumerate() {
             let one = *(next.get(i).as_deref().unwrap());
-            let two = *(values[i].get(j).as_deref().unwrap());
-            if (one == true) && (two == true) {
-                r[j] += 1;
+            let two = *(value.get(j).as_deref().unwrap());
+            if one && two {
+                *parity += 1;
             }
         }
     }
-    for index in 0..r.len() {
-        if r[index].rem_euclid(2) == 1 {
+    for (index, parity) in r.iter().enumerate() {
+        if parity.rem_euclid(2) == 1 {
             next.set(INDEXES[index], true);
         } else {
             next.set(INDEXES[index], false);
@@ -38,7 +38,7 @@ pub fn encode(mut block: [u8; 7]) -> Vec<u8> {
     //println!("{}", next);
     // We put the parity bits at the top for performance reasons
     // Vec<bool> len u32 == 32
-    return next.into();
+    next.into()
 }
 pub fn decode(mut code: [u8; 8], repair: bool, block_count: usize) -> (Option<Vec<u8>>, usize) {
     // We have an error
@@ -46,24 +46,24 @@ pub fn decode(mut code: [u8; 8], repair: bool, block_count: usize) -> (Option<Ve
     for i in 1u8..65u8 {
         values.push(i.view_bits::<bv::Lsb0>().to_bitvec());
     }
-    let mut next = code.view_bits_mut::<bv::Msb0>();
+    let next = code.view_bits_mut::<bv::Msb0>();
     //println!("{}", next);
     let mut error_count = 0;
     let mut has_error = false;
     loop {
         let mut r = [0i32; INDEXES.len()];
-        for j in 0..INDEXES.len() {
-            for i in 0..next.len() {
+        for (j, parity) in r.iter_mut().enumerate() {
+            for (i, value) in values.iter().enumerate() {
                 let one = *(next.get(i).as_deref().unwrap());
-                let two = *(values[i].get(j).as_deref().unwrap());
-                if (one == true) && (two == true) {
-                    r[j] += 1;
+                let two = *(value.get(j).as_deref().unwrap());
+                if one && two {
+                    *parity += 1;
                 }
             }
         }
         let mut error_vec = bv::BitVec::<u8, bv::Lsb0>::with_capacity(r.len());
-        for index in 0..r.len() {
-            if r[index].rem_euclid(2) == 1 {
+        for parity in &r {
+            if parity.rem_euclid(2) == 1 {
                 error_vec.push(true);
                 has_error = true;
             } else {
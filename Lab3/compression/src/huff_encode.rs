@@ -1,23 +1,22 @@
 use std::{
     collections::{hash_map::RandomState, HashMap},
-    convert::TryFrom,
-    convert::TryInto,
     fmt::Debug,
     hash::BuildHasher,
     hash::Hash,
     io::Error,
     marker::PhantomData,
-    mem,
-    mem::size_of,
-    str::FromStr,
 };
+
+use crate::huff_tree::size_of_bits;
 pub trait HuffLetter: Clone + Eq + Hash + Debug {}
 /// Trait specifying that the given HuffLetter can be converted
 /// into bytes *(returns `Box<[u8]>`)* and
 /// can be created from bytes (`&[u8]`),
 /// so the [`HuffTree`][crate::tree::HuffTree] can be represented in binary.
 ///
-/// Implemented by default for every integer
+/// Implemented for `char`, as its UTF-8 bytes padded with zeros to 4 bytes,
+/// and for the fixed-width unsigned integers, so a tree reads back the same
+/// on every platform
 pub trait HuffLetterAsBytes: HuffLetter {
     fn try_from_be_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>>;
     fn as_be_bytes(&self) -> Box<[u8]>;
@@ -34,7 +33,44 @@ macro_rules! primitive_letter_impl{
 primitive_letter_impl! {
     char,
     &str,
-    String
+    String,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize
+}
+impl HuffLetterAsBytes for char {
+    fn try_from_be_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::str::from_utf8(bytes)?;
+        Ok(text.chars().next().ok_or("no letter in the bytes")?)
+    }
+    fn as_be_bytes(&self) -> Box<[u8]> {
+        let mut bytes = [0u8; 4];
+        self.encode_utf8(&mut bytes);
+        Box::new(bytes)
+    }
+}
+/// Implements `HuffLetterAsBytes` for every provided integer type
+macro_rules! integer_letter_as_bytes_impl{
+    {$($type:ty),+} => {
+        $(
+        impl HuffLetterAsBytes for $type {
+            fn try_from_be_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+                Ok(Self::from_be_bytes(bytes.try_into()?))
+            }
+            fn as_be_bytes(&self) -> Box<[u8]> {
+                Box::new(self.to_be_bytes())
+            }
+        }
+        )+
+    };
+}
+integer_letter_as_bytes_impl! {
+    u8,
+    u16,
+    u32,
+    u64
 }
 #[derive(Debug, Clone)]
 pub struct CompressData {
@@ -62,7 +98,7 @@ impl CompressData {
             comp_bytes,
             padding_bits,
             huff_tree,
-            _typebind: PhantomData::default(),
+            _typebind: PhantomData,
         }
     }
     pub fn into_inner(self) -> (Vec<u8>, u8, Tree) {
@@ -106,9 +142,12 @@ pub fn compress_with_tree(letters: String, huff_tree: Tree) -> Result<CompressDa
 
     Ok(CompressData::new(comp_letters, padding_bits, huff_tree))
 }
-fn set_codes_in_child_branches(parent: &mut Node, parent_code: Option<BitVec<u8, Msb0>>) {
+fn set_codes_in_child_branches<L: HuffLetter>(
+    parent: &mut Node<L>,
+    parent_code: Option<BitVec<u8, Msb0>>,
+) {
     if parent.has_children() {
-        let set_code = |child: &mut Node, pos| {
+        let set_code = |child: &mut Node<L>, pos| {
             // append pos_in_parent to parent_code and set the newly created code on child
             let mut child_code = BitVec::with_capacity(1);
             if let Some(parent_code) = parent_code {
@@ -132,18 +171,19 @@ pub fn calc_padding_bits(bit_count: usize) -> u8 {
         _ => n,
     }
 }
-use bitvec::{prelude::*, view::AsBits};
+use bitvec::prelude::*;
+/// Huffman tree over letters of type `L`, `char` by default
 #[derive(Debug, Clone)]
-pub struct Tree {
-    root: Box<Node>,
+pub struct Tree<L: HuffLetter = char> {
+    root: Box<Node<L>>,
 }
-pub struct ChildrenIter<'a> {
-    parent: &'a Node,
+pub struct ChildrenIter<'a, L: HuffLetter = char> {
+    parent: &'a Node<L>,
     child_pos: u8,
 }
 
-impl<'a> Iterator for ChildrenIter<'a> {
-    type Item = &'a Node;
+impl<'a, L: HuffLetter> Iterator for ChildrenIter<'a, L> {
+    type Item = &'a Node<L>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.child_pos {
@@ -160,39 +200,42 @@ impl<'a> Iterator for ChildrenIter<'a> {
     }
 }
 
-impl<'a> ChildrenIter<'a> {
+impl<'a, L: HuffLetter> ChildrenIter<'a, L> {
     /// Initialize a new ```ChildrenIter``` over
     /// the children of the provided ```HuffBranch```
-    pub fn new(parent: &'a Node) -> Self {
+    pub fn new(parent: &'a Node<L>) -> Self {
         ChildrenIter {
             parent,
             child_pos: 0,
         }
     }
 }
-impl Tree {
+impl<L: HuffLetter> Tree<L> {
+    pub fn root(&self) -> &Node<L> {
+        &self.root
+    }
     pub fn read_codes_with_hasher<S: BuildHasher>(
         &self,
         hash_builder: S,
-    ) -> HashMap<char, BitVec<u8, Msb0>, S> {
+    ) -> HashMap<L, BitVec<u8, Msb0>, S> {
         /// Recursively insert letters to codes into the given HashMap<L, BitVec<Msb0, u8>>
-        fn set_codes<S: BuildHasher>(
-            codes: &mut HashMap<char, BitVec<u8, Msb0>, S>,
-            root: &Node,
+        fn set_codes<L: HuffLetter, S: BuildHasher>(
+            codes: &mut HashMap<L, BitVec<u8, Msb0>, S>,
+            root: &Node<L>,
             pos_in_parent: bool,
         ) {
             if let Some(children_iter) = root.children_iter() {
                 for (pos, child) in children_iter.enumerate() {
                     let branch = child;
                     if let Some(letter) = branch.letter() {
-                        codes.insert(letter.clone(), branch.code().unwrap().clone());
+                        codes.insert(letter, branch.code().unwrap().clone());
                     } else {
                         set_codes(codes, child, pos != 0);
                     }
                 }
             } else {
                 codes.insert(
-                    root.letter().unwrap().clone(),
+                    root.letter().unwrap(),
                     bitvec![u8,Msb0; pos_in_parent as u8;1],
                 );
             }
@@ -205,55 +248,14 @@ impl Tree {
             set_codes(&mut codes, root.right_child().unwrap(), true);
             codes
         } else {
-            codes.insert(root.letter().unwrap().clone(), bitvec![u8,Msb0; 0;1]);
+            codes.insert(root.letter().unwrap(), bitvec![u8,Msb0; 0;1]);
             codes
         }
     }
-    pub fn read_codes(&self) -> HashMap<char, BitVec<u8, Msb0>> {
+    pub fn read_codes(&self) -> HashMap<L, BitVec<u8, Msb0>> {
         self.read_codes_with_hasher(RandomState::default())
     }
-    pub fn as_bin(&self) -> BitVec<u8, Msb0> {
-        /// Recursively push bits to the given BitVec<Msb0, u8>
-        /// depending on the branches you encounter:
-        /// * 0 being a letter branch (followed by a letter encoded in binary)
-        /// * 1 being a joint branch
-        fn set_tree_as_bin(tree_bin: &mut BitVec<u8, Msb0>, root: &Node) {
-            let root = root;
-            let children_iter = root.children_iter();
 
-            // has children -> joint branch
-            if let Some(children_iter) = children_iter {
-                // 1 means joint branch
-                tree_bin.push(true);
-
-                // call set_bin on children
-                for child in children_iter {
-                    set_tree_as_bin(tree_bin, &child);
-                }
-            }
-            // no children -> letter branch
-            else {
-                // 0 means letter branch
-                tree_bin.push(false);
-                let mut vc = root.letter().unwrap().to_string().as_bytes().to_vec();
-                for _i in 0..(4 - vc.len()) {
-                    vc.push(0u8);
-                }
-                vc.shrink_to_fit();
-                let mut tmp = [0u8; 4];
-                let c = root.letter().unwrap().encode_utf8(&mut tmp);
-
-                // convert the letter to bytes and push the bytes' bits into the tree_bin
-                for byte in BitVec::<u8, Msb0>::from_vec(tmp.to_vec()) {
-                    tree_bin.push(byte);
-                }
-            }
-        }
-
-        let mut treebin = BitVec::new();
-        set_tree_as_bin(&mut treebin, &self.root);
-        treebin
-    }
     pub fn assign_codes(&mut self) {
         if self.root.has_children() {
             set_codes_in_child_branches(&mut self.root, None);
@@ -265,13 +267,13 @@ impl Tree {
             });
         }
     }
-    pub fn build_from_weights(weights: HashMap<char, usize>) -> Self {
-        let mut p: Vec<Box<Node>> = weights
-            .iter()
+    pub fn build_from_weights(weights: HashMap<L, usize>) -> Self {
+        let mut p: Vec<Box<Node<L>>> = weights
+            .into_iter()
             .map(|x| {
                 Box::new(Node {
-                    letter: Some(*x.0),
-                    freq: *x.1,
+                    letter: Some(x.0),
+                    freq: x.1,
                     code: None,
                     left: None,
                     right: None,
@@ -279,7 +281,7 @@ impl Tree {
             })
             .collect();
         while p.len() > 1 {
-            p.sort_by(|a, b| (&(b.freq)).cmp(&(a.freq)));
+            p.sort_by_key(|node| std::cmp::Reverse(node.freq));
             let a = p.pop().unwrap();
             let b = p.pop().unwrap();
             let mut c = Box::new(Node {
@@ -297,17 +299,134 @@ impl Tree {
             root: p.pop().unwrap(),
         }
     }
+    /// The same tree with every letter replaced by `f` of it, e.g. by an index
+    /// into a table, so letters of no fixed size can be written with [`Tree::as_bin`]
+    pub fn map_letters<M: HuffLetter>(&self, f: &mut impl FnMut(&L) -> M) -> Tree<M> {
+        fn map_node<L: HuffLetter, M: HuffLetter>(
+            node: &Node<L>,
+            f: &mut impl FnMut(&L) -> M,
+        ) -> Node<M> {
+            Node {
+                letter: node.letter.as_ref().map(&mut *f),
+                freq: node.freq,
+                code: node.code.clone(),
+                left: node.left.as_ref().map(|child| Box::new(map_node(child, f))),
+                right: node
+                    .right
+                    .as_ref()
+                    .map(|child| Box::new(map_node(child, f))),
+            }
+        }
+        Tree {
+            root: Box::new(map_node(&self.root, f)),
+        }
+    }
+}
+impl<L: HuffLetterAsBytes> Tree<L> {
+    pub fn as_bin(&self) -> BitVec<u8, Msb0> {
+        /// Recursively push bits to the given BitVec<Msb0, u8>
+        /// depending on the branches you encounter:
+        /// * 0 being a letter branch (followed by a letter encoded in binary)
+        /// * 1 being a joint branch
+        fn set_tree_as_bin<L: HuffLetterAsBytes>(tree_bin: &mut BitVec<u8, Msb0>, root: &Node<L>) {
+            let children_iter = root.children_iter();
+
+            // has children -> joint branch
+            if let Some(children_iter) = children_iter {
+                // 1 means joint branch
+                tree_bin.push(true);
+
+                // call set_bin on children
+                for child in children_iter {
+                    set_tree_as_bin(tree_bin, child);
+                }
+            }
+            // no children -> letter branch
+            else {
+                // 0 means letter branch
+                tree_bin.push(false);
+                // convert the letter to bytes and push the bytes' bits into the tree_bin
+                let bytes = root.letter().unwrap().as_be_bytes();
+                tree_bin.extend_from_bitslice(bytes.view_bits::<Msb0>());
+            }
+        }
+
+        let mut treebin = BitVec::new();
+        set_tree_as_bin(&mut treebin, &self.root);
+        treebin
+    }
+    /// Reads the tree written by [`as_bin`][Tree::as_bin], every letter taking
+    /// `size_of::<L>()` bytes, and assigns the codes
+    pub fn try_from_bin(bin: BitVec<u8, Msb0>) -> Result<Self, Error> {
+        fn invalid(reason: &str) -> Error {
+            Error::new(std::io::ErrorKind::InvalidData, reason.to_string())
+        }
+        /// Recursively reads branches and their children from the given bits
+        /// When finding a 1 -> recurses to get children,
+        /// and when a 0 -> ends recursion returning a letter branch
+        fn read_branches_from_bits<L: HuffLetterAsBytes>(
+            bits: &mut bitvec::slice::Iter<u8, Msb0>,
+        ) -> Result<Node<L>, Error> {
+            let joint = *bits
+                .next()
+                .ok_or_else(|| invalid("Provided BitVec is too short for an encoded HuffTree"))?;
+            let mut node = Node {
+                letter: None,
+                freq: 0,
+                code: None,
+                left: None,
+                right: None,
+            };
+            if joint {
+                node.left = Some(Box::new(read_branches_from_bits(bits)?));
+                node.right = Some(Box::new(read_branches_from_bits(bits)?));
+            } else {
+                let letter_bits =
+                    bits.as_bitslice()
+                        .get(..size_of_bits::<L>())
+                        .ok_or_else(|| {
+                            invalid("Provided BitVec is too short for an encoded HuffTree")
+                        })?;
+                let letter = L::try_from_be_bytes(
+                    &letter_bits
+                        .chunks(8)
+                        .map(|byte| byte.load_be::<u8>())
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(|err| {
+                    invalid(&format!("Invalid letter in an encoded HuffTree: {}", err))
+                })?;
+                node.letter = Some(letter);
+                for _ in 0..size_of_bits::<L>() {
+                    bits.next();
+                }
+            }
+            Ok(node)
+        }
+        let mut bits = bin.iter();
+        let root = read_branches_from_bits(&mut bits)?;
+        if bits.next().is_some() {
+            return Err(invalid(
+                "Provided BitVec is too big for an encoded HuffTree",
+            ));
+        }
+        let mut tree = Tree {
+            root: Box::new(root),
+        };
+        tree.assign_codes();
+        Ok(tree)
+    }
 }
 #[derive(Debug, Clone)]
-pub struct Node {
-    letter: Option<char>,
+pub struct Node<L: HuffLetter = char> {
+    letter: Option<L>,
     freq: usize,
     code: Option<BitVec<u8, Msb0>>,
-    left: Option<Box<Node>>,
-    right: Option<Box<Node>>,
+    left: Option<Box<Node<L>>>,
+    right: Option<Box<Node<L>>>,
 }
-impl Node {
-    pub fn children_iter(&self) -> Option<ChildrenIter> {
+impl<L: HuffLetter> Node<L> {
+    pub fn children_iter(&self) -> Option<ChildrenIter<'_, L>> {
         if self.has_children() {
             Some(ChildrenIter::new(self))
         } else {
@@ -317,21 +436,21 @@ impl Node {
     pub fn code(&self) -> Option<&BitVec<u8, Msb0>> {
         self.code.as_ref()
     }
-    pub fn letter(&self) -> Option<char> {
-        self.letter
+    pub fn letter(&self) -> Option<L> {
+        self.letter.clone()
     }
-    pub fn left_child(&self) -> Option<&Node> {
+    pub fn left_child(&self) -> Option<&Node<L>> {
         self.left.as_deref()
     }
 
-    pub fn right_child(&self) -> Option<&Node> {
+    pub fn right_child(&self) -> Option<&Node<L>> {
         self.right.as_deref()
     }
-    pub fn left_child_mut(&mut self) -> Option<&mut Node> {
+    pub fn left_child_mut(&mut self) -> Option<&mut Node<L>> {
         self.left.as_deref_mut()
     }
 
-    pub fn right_child_mut(&mut self) -> Option<&mut Node> {
+    pub fn right_child_mut(&mut self) -> Option<&mut Node<L>> {
         self.right.as_deref_mut()
     }
     pub fn has_children(&self) -> bool {
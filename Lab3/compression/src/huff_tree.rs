@@ -34,7 +34,7 @@ impl CompressData {
             comp_bytes,
             padding_bits,
             huff_tree,
-            _typebind: PhantomData::default(),
+            _typebind: PhantomData,
         }
     }
     pub fn into_inner(self) -> (Vec<u8>, u8, Tree) {
@@ -78,6 +78,28 @@ pub fn compress_with_tree(letters: String, huff_tree: Tree) -> Result<CompressDa
 
     Ok(CompressData::new(comp_letters, padding_bits, huff_tree))
 }
+/// Decodes `comp_bytes` written by [`compress_with_tree`], leaving out the
+/// `padding_bits` at the end of the last byte
+pub fn decompress(comp_bytes: &[u8], padding_bits: u8, huff_tree: &Tree) -> String {
+    let bits = comp_bytes.view_bits::<Msb0>();
+    let bits = &bits[..bits.len().saturating_sub(padding_bits as usize)];
+    let mut letters = String::new();
+    let mut current_branch = huff_tree.root();
+    for bit in bits.iter().by_vals() {
+        if current_branch.has_children() {
+            current_branch = if bit {
+                current_branch.right_child().unwrap()
+            } else {
+                current_branch.left_child().unwrap()
+            };
+        }
+        if !current_branch.has_children() {
+            letters.push(current_branch.letter().unwrap());
+            current_branch = huff_tree.root();
+        }
+    }
+    letters
+}
 fn set_codes_in_child_branches(parent: &mut Node, parent_code: Option<BitVec<u8, Msb0>>) {
     if parent.has_children() {
         let set_code = |child: &mut Node, pos| {
@@ -254,14 +276,14 @@ impl Tree {
                 for (pos, child) in children_iter.enumerate() {
                     let branch = child;
                     if let Some(letter) = branch.letter() {
-                        codes.insert(letter.clone(), branch.code().unwrap().clone());
+                        codes.insert(letter, branch.code().unwrap().clone());
                     } else {
                         set_codes(codes, child, pos != 0);
                     }
                 }
             } else {
                 codes.insert(
-                    root.letter().unwrap().clone(),
+                    root.letter().unwrap(),
                     bitvec![u8,Msb0; pos_in_parent as u8;1],
                 );
             }
@@ -274,7 +296,7 @@ impl Tree {
             set_codes(&mut codes, root.right_child().unwrap(), true);
             codes
         } else {
-            codes.insert(root.letter().unwrap().clone(), bitvec![u8,Msb0; 0;1]);
+            codes.insert(root.letter().unwrap(), bitvec![u8,Msb0; 0;1]);
             codes
         }
     }
@@ -287,7 +309,6 @@ impl Tree {
         /// * 0 being a letter branch (followed by a letter encoded in binary)
         /// * 1 being a joint branch
         fn set_tree_as_bin(tree_bin: &mut BitVec<u8, Msb0>, root: &Node) {
-            let root = root;
             let children_iter = root.children_iter();
 
             // has children -> joint branch
@@ -297,7 +318,7 @@ impl Tree {
 
                 // call set_bin on children
                 for child in children_iter {
-                    set_tree_as_bin(tree_bin, &child);
+                    set_tree_as_bin(tree_bin, child);
                 }
             }
             // no children -> letter branch
@@ -343,7 +364,7 @@ impl Tree {
             })
             .collect();
         while p.len() > 1 {
-            p.sort_by(|a, b| (&(b.freq)).cmp(&(a.freq)));
+            p.sort_by_key(|node| std::cmp::Reverse(node.freq));
             let a = p.pop().unwrap();
             let b = p.pop().unwrap();
             let mut c = Box::new(Node {
@@ -371,7 +392,7 @@ pub struct Node {
     right: Option<Box<Node>>,
 }
 impl Node {
-    pub fn children_iter(&self) -> Option<ChildrenIter> {
+    pub fn children_iter(&self) -> Option<ChildrenIter<'_>> {
         if self.has_children() {
             Some(ChildrenIter::new(self))
         } else {
@@ -381,7 +402,7 @@ impl Node {
     pub fn new(letter: Option<char>, weight: usize, children: Option<(Node, Node)>) -> Self {
         if let Some(children) = children {
             Node {
-                letter: letter,
+                letter,
                 freq: weight,
                 code: None,
                 left: Some(Box::new(children.0)),
@@ -389,7 +410,7 @@ impl Node {
             }
         } else {
             Node {
-                letter: letter,
+                letter,
                 freq: weight,
                 code: None,
                 left: None,
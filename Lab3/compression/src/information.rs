@@ -0,0 +1,131 @@
+//! Information measures of discrete distributions, all in bits.
+//!
+//! Distributions are slices of probabilities summing to 1, joint
+//! distributions are matrices whose entries sum to 1. Zero probabilities
+//! contribute nothing, following the convention `0 log 0 = 0`.
+
+/// `-p log2 p`, taken as 0 for `p = 0`
+fn surprisal(p: f64) -> f64 {
+    if p > 0.0 {
+        -p * p.log2()
+    } else {
+        0.0
+    }
+}
+
+/// Shannon entropy H(X)
+pub fn entropy(probabilities: &[f64]) -> f64 {
+    probabilities.iter().map(|&p| surprisal(p)).sum()
+}
+
+/// Entropy of the relative frequencies given by `counts`
+pub fn entropy_of_counts<I: IntoIterator<Item = usize>>(counts: I) -> f64 {
+    let counts = counts.into_iter().collect::<Vec<_>>();
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .map(|&count| surprisal(count as f64 / total as f64))
+        .sum()
+}
+
+/// Largest entropy of an alphabet of `n` symbols, log2 n
+pub fn max_entropy(n: usize) -> f64 {
+    (n as f64).log2()
+}
+
+/// Joint entropy H(X, Y) of the joint distribution `joint[x][y]`
+pub fn joint_entropy(joint: &[Vec<f64>]) -> f64 {
+    joint.iter().map(|row| entropy(row)).sum()
+}
+
+/// Conditional entropy H(Y|X) of a source that is in state `x` with
+/// probability `weights[x]` and then emits `y` with probability `rows[x][y]`,
+/// e.g. the stationary distribution and the transition matrix of a Markov chain
+pub fn conditional_entropy(weights: &[f64], rows: &[Vec<f64>]) -> f64 {
+    weights
+        .iter()
+        .zip(rows)
+        .map(|(&weight, row)| weight * entropy(row))
+        .sum()
+}
+
+/// Mutual information I(X; Y) = H(X) + H(Y) - H(X, Y) of `joint[x][y]`
+pub fn mutual_information(joint: &[Vec<f64>]) -> f64 {
+    let columns = joint.iter().map(Vec::len).max().unwrap_or(0);
+    let x = joint
+        .iter()
+        .map(|row| row.iter().sum())
+        .collect::<Vec<f64>>();
+    let y = (0..columns)
+        .map(|j| joint.iter().filter_map(|row| row.get(j)).sum())
+        .collect::<Vec<f64>>();
+    // rounding can push it a hair below zero
+    (entropy(&x) + entropy(&y) - joint_entropy(joint)).max(0.0)
+}
+
+/// Kullback–Leibler divergence D(P || Q), infinite when `q` gives
+/// probability 0 to something `p` does not
+pub fn kl_divergence(p: &[f64], q: &[f64]) -> f64 {
+    p.iter()
+        .zip(q)
+        .filter(|(&p, _)| p > 0.0)
+        .map(|(&p, &q)| {
+            if q > 0.0 {
+                p * (p / q).log2()
+            } else {
+                f64::INFINITY
+            }
+        })
+        .sum()
+}
+
+/// Redundancy `1 - H / log2 n` of a source with entropy `entropy`
+/// over an alphabet of `n` symbols, 0 for a one letter alphabet
+pub fn redundancy(entropy: f64, n: usize) -> f64 {
+    if n < 2 {
+        0.0
+    } else {
+        1.0 - entropy / max_entropy(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn entropy_of_known_distributions() {
+        assert!(close(entropy(&[0.5, 0.5]), 1.0));
+        assert!(close(entropy(&[0.25; 4]), 2.0));
+        assert!(close(entropy(&[0.5, 0.25, 0.125, 0.125]), 1.75));
+        assert!(close(entropy(&[1.0, 0.0]), 0.0));
+        assert!(close(entropy_of_counts(vec![2, 1, 1]), 1.5));
+    }
+
+    #[test]
+    fn joint_measures_agree() {
+        let independent = vec![vec![0.25, 0.25], vec![0.25, 0.25]];
+        assert!(close(joint_entropy(&independent), 2.0));
+        assert!(close(mutual_information(&independent), 0.0));
+        let copy = vec![vec![0.5, 0.0], vec![0.0, 0.5]];
+        assert!(close(mutual_information(&copy), 1.0));
+        let rows = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        assert!(close(conditional_entropy(&[0.5, 0.5], &rows), 0.0));
+    }
+
+    #[test]
+    fn divergence_and_redundancy() {
+        assert!(close(kl_divergence(&[0.5, 0.5], &[0.5, 0.5]), 0.0));
+        assert!(close(kl_divergence(&[1.0, 0.0], &[0.5, 0.5]), 1.0));
+        assert!(kl_divergence(&[0.5, 0.5], &[1.0, 0.0]).is_infinite());
+        assert!(close(redundancy(1.0, 4), 0.5));
+        assert!(close(redundancy(0.0, 1), 0.0));
+    }
+}
@@ -1,4 +1,16 @@
+pub mod ans;
+pub mod arithmetic;
+pub mod block;
+pub mod bwt;
+pub mod container;
+pub mod context;
+pub mod dary;
 pub mod hamming;
 pub mod huff_encode;
 pub mod huff_tree;
+pub mod information;
+pub mod lz78;
+pub mod lzss;
+pub mod prefix_codes;
+pub mod tokens;
 pub mod utils;
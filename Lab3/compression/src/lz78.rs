@@ -0,0 +1,304 @@
+//! LZ78 and LZW, dictionary coders that need no model of the source.
+//!
+//! Both grow a dictionary of phrases while reading the input, so that the
+//! decoder can rebuild it from the codes alone. LZ78 writes the index of the
+//! longest known phrase followed by the byte that extends it into a new one.
+//! LZW starts with every byte in the dictionary and writes indices only, the
+//! byte being the first one of the next phrase.
+//!
+//! Every index takes as few bits as the dictionary size at that point needs.
+//! Once it reaches `2^max_bits` phrases, the dictionary is reset on both sides.
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Smallest dictionary, LZW needs room beyond its 256 single bytes
+pub const MIN_BITS: u8 = 9;
+pub const MAX_BITS: u8 = 24;
+/// Indices of at most 16 bits, a dictionary of 65536 phrases
+pub const DEFAULT_BITS: u8 = 16;
+/// LZW code of the end of data, the first phrases are the bytes
+const LZW_END: u32 = 256;
+const LZW_FIRST: u32 = 257;
+
+/// Number of bits of `value`, 1 for 0
+fn width(value: u32) -> u32 {
+    (u32::BITS - value.leading_zeros()).max(1)
+}
+
+fn push_bits(bits: &mut BitVec<u8, Msb0>, value: u32, width: u32) {
+    for i in (0..width).rev() {
+        bits.push((value >> i) & 1 == 1);
+    }
+}
+
+fn check_bits(max_bits: u8) -> Result<(), Error> {
+    if (MIN_BITS..=MAX_BITS).contains(&max_bits) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "dictionary of {} bits is not in {}..={}",
+                max_bits, MIN_BITS, MAX_BITS
+            ),
+        ))
+    }
+}
+
+/// Reads codes of the widths the decoder asks for
+struct BitReader<'a> {
+    bits: bitvec::slice::Iter<'a, u8, Msb0>,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, width: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        for _ in 0..width {
+            let bit = self.bits.next().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "dictionary coded data ends without the end code".to_string(),
+                )
+            })?;
+            value = value << 1 | *bit as u32;
+        }
+        Ok(value)
+    }
+}
+
+fn invalid_code(code: u32) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("dictionary code {} is not in the dictionary yet", code),
+    )
+}
+
+/// Writes the bytes of phrase `code` to `data`, the phrases being `(prefix, last byte)`
+/// pairs down to the first of the dictionary, the empty LZ78 one or an LZW byte
+fn push_phrase(data: &mut Vec<u8>, phrases: &[(u32, u8)], mut code: u32, first: u32) {
+    let start = data.len();
+    while code >= first {
+        let (prefix, byte) = phrases[code as usize];
+        data.push(byte);
+        code = prefix;
+    }
+    if first == LZW_FIRST {
+        data.push(code as u8);
+    }
+    data[start..].reverse();
+}
+
+/// Codes `data` as LZ78 pairs, returning the bytes and the number of padding bits in the last one
+pub fn lz78_compress(data: &[u8], max_bits: u8) -> Result<(Vec<u8>, u8), Error> {
+    check_bits(max_bits)?;
+    let capacity = 1u32 << max_bits;
+    let mut bits = BitVec::<u8, Msb0>::new();
+    // phrase 0 is the empty one, every other is a known phrase and a byte
+    let mut dictionary = HashMap::new();
+    let mut phrases = vec![(0u32, 0u8)];
+    let mut phrase = 0;
+    for &byte in data {
+        if let Some(&next) = dictionary.get(&(phrase, byte)) {
+            phrase = next;
+            continue;
+        }
+        push_bits(&mut bits, phrase, width(phrases.len() as u32));
+        push_bits(&mut bits, byte as u32, 8);
+        dictionary.insert((phrase, byte), phrases.len() as u32);
+        phrases.push((phrase, byte));
+        if phrases.len() as u32 == capacity {
+            dictionary.clear();
+            phrases.truncate(1);
+        }
+        phrase = 0;
+    }
+    // a phrase left over is written as its prefix and last byte once more
+    if phrase != 0 {
+        let (prefix, byte) = phrases[phrase as usize];
+        push_bits(&mut bits, prefix, width(phrases.len() as u32));
+        push_bits(&mut bits, byte as u32, 8);
+        phrases.push((prefix, byte));
+        if phrases.len() as u32 == capacity {
+            phrases.truncate(1);
+        }
+    }
+    // the index one past the dictionary ends the data
+    push_bits(&mut bits, phrases.len() as u32, width(phrases.len() as u32));
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// Decodes the bytes written by [`lz78_compress`]
+pub fn lz78_decompress(bytes: &[u8], max_bits: u8) -> Result<Vec<u8>, Error> {
+    check_bits(max_bits)?;
+    let capacity = 1u32 << max_bits;
+    let mut reader = BitReader {
+        bits: bytes.view_bits::<Msb0>().iter(),
+    };
+    let mut phrases = vec![(0u32, 0u8)];
+    let mut data = Vec::new();
+    loop {
+        let size = phrases.len() as u32;
+        let code = reader.read(width(size))?;
+        if code == size {
+            return Ok(data);
+        }
+        if code > size {
+            return Err(invalid_code(code));
+        }
+        let byte = reader.read(8)? as u8;
+        push_phrase(&mut data, &phrases, code, 1);
+        data.push(byte);
+        phrases.push((code, byte));
+        if phrases.len() as u32 == capacity {
+            phrases.truncate(1);
+        }
+    }
+}
+
+/// Writes LZW code number `written` since the reset, telling if the dictionary is full.
+/// The decoder learns a phrase one code after the encoder, so the widths follow
+/// the codes written rather than the phrases known
+fn push_lzw_code(bits: &mut BitVec<u8, Msb0>, code: u32, written: &mut u32, capacity: u32) -> bool {
+    push_bits(bits, code, width(LZW_END + *written));
+    *written += 1;
+    *written == capacity - LZW_FIRST
+}
+
+/// Codes `data` with LZW, returning the bytes and the number of padding bits in the last one
+pub fn lzw_compress(data: &[u8], max_bits: u8) -> Result<(Vec<u8>, u8), Error> {
+    check_bits(max_bits)?;
+    let capacity = 1u32 << max_bits;
+    let mut bits = BitVec::<u8, Msb0>::new();
+    let mut dictionary = HashMap::new();
+    let mut next = LZW_FIRST;
+    let mut written = 0;
+    let mut phrase: Option<u32> = None;
+    for &byte in data {
+        let current = match phrase {
+            Some(current) => current,
+            None => {
+                phrase = Some(byte as u32);
+                continue;
+            }
+        };
+        if let Some(&longer) = dictionary.get(&(current, byte)) {
+            phrase = Some(longer);
+            continue;
+        }
+        dictionary.insert((current, byte), next);
+        next += 1;
+        if push_lzw_code(&mut bits, current, &mut written, capacity) {
+            dictionary.clear();
+            next = LZW_FIRST;
+            written = 0;
+        }
+        phrase = Some(byte as u32);
+    }
+    if let Some(current) = phrase {
+        if push_lzw_code(&mut bits, current, &mut written, capacity) {
+            written = 0;
+        }
+    }
+    push_lzw_code(&mut bits, LZW_END, &mut written, capacity);
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// Decodes the bytes written by [`lzw_compress`]
+pub fn lzw_decompress(bytes: &[u8], max_bits: u8) -> Result<Vec<u8>, Error> {
+    check_bits(max_bits)?;
+    let capacity = 1u32 << max_bits;
+    let mut reader = BitReader {
+        bits: bytes.view_bits::<Msb0>().iter(),
+    };
+    // the bytes and the end code come first, so that a code is its index
+    let mut phrases: Vec<(u32, u8)> = (0..=LZW_END).map(|code| (0, code as u8)).collect();
+    let mut data = Vec::new();
+    let mut written = 0;
+    let mut previous: Option<u32> = None;
+    loop {
+        let code = reader.read(width(LZW_END + written))?;
+        if code == LZW_END {
+            return Ok(data);
+        }
+        let next = phrases.len() as u32;
+        let start = data.len();
+        match previous {
+            // the phrase the encoder added just before writing it: the
+            // previous one followed by its own first byte
+            Some(previous) if code == next => {
+                push_phrase(&mut data, &phrases, previous, LZW_FIRST);
+                data.push(data[start]);
+            }
+            _ if code >= next => return Err(invalid_code(code)),
+            _ => push_phrase(&mut data, &phrases, code, LZW_FIRST),
+        }
+        if let Some(previous) = previous {
+            phrases.push((previous, data[start]));
+        }
+        previous = Some(code);
+        written += 1;
+        if written == capacity - LZW_FIRST {
+            phrases.truncate(LZW_FIRST as usize);
+            previous = None;
+            written = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abababababababab".to_vec(),
+            b"TOBEORNOTTOBEORTOBEORNOT#".to_vec(),
+            std::iter::repeat_n(b'a', 5000).collect(),
+            (0..20000u32).map(|i| (i * i % 251) as u8).collect(),
+        ]
+    }
+
+    #[test]
+    fn lz78_roundtrip() {
+        for data in samples() {
+            let (bytes, _) = lz78_compress(&data, DEFAULT_BITS).unwrap();
+            assert_eq!(lz78_decompress(&bytes, DEFAULT_BITS).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn lzw_roundtrip() {
+        for data in samples() {
+            let (bytes, _) = lzw_compress(&data, DEFAULT_BITS).unwrap();
+            assert_eq!(lzw_decompress(&bytes, DEFAULT_BITS).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn dictionary_reset() {
+        // the smallest dictionary fills up many times over these
+        for data in samples() {
+            let (bytes, _) = lz78_compress(&data, MIN_BITS).unwrap();
+            assert_eq!(lz78_decompress(&bytes, MIN_BITS).unwrap(), data);
+            let (bytes, _) = lzw_compress(&data, MIN_BITS).unwrap();
+            assert_eq!(lzw_decompress(&bytes, MIN_BITS).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn repetitive_input_shrinks() {
+        let data: Vec<u8> = b"the cat sat on the mat ".repeat(400);
+        let (lz78, _) = lz78_compress(&data, DEFAULT_BITS).unwrap();
+        let (lzw, _) = lzw_compress(&data, DEFAULT_BITS).unwrap();
+        assert!(lz78.len() * 4 < data.len());
+        assert!(lzw.len() * 4 < data.len());
+        assert!(lzw_decompress(&lzw[..lzw.len() / 2], DEFAULT_BITS).is_err());
+    }
+}
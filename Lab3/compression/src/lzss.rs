@@ -0,0 +1,355 @@
+//! LZSS dictionary coding with Huffman coded symbols, as in deflate.
+//!
+//! The input bytes are parsed into literals and back-references to the
+//! previous `window` bytes, found through hash chains of the 3-byte prefixes.
+//! Literals and match lengths share one alphabet, match distances have another,
+//! both with deflate's codes and extra bits, and each gets its own
+//! [`Tree<u16>`][Tree]:
+//!
+//! ```text
+//! 0..=255   literal byte
+//! 256       end of data
+//! 257..=285 match length 3..=258, followed by its extra bits, then the
+//!           distance code 0..=29 and its extra bits
+//! ```
+use crate::huff_encode::Tree;
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind},
+};
+
+/// Largest window deflate's distance codes reach
+pub const MAX_WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const END: u16 = 256;
+const HASH_BITS: u32 = 15;
+
+/// Smallest length of every length code from 257, and the number of extra bits
+#[rustfmt::skip]
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+    (11, 1), (13, 1), (15, 1), (17, 1), (19, 2), (23, 2), (27, 2), (31, 2),
+    (35, 3), (43, 3), (51, 3), (59, 3), (67, 4), (83, 4), (99, 4), (115, 4),
+    (131, 5), (163, 5), (195, 5), (227, 5), (258, 0),
+];
+/// Smallest distance of every distance code, and the number of extra bits
+#[rustfmt::skip]
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0), (2, 0), (3, 0), (4, 0), (5, 1), (7, 1), (9, 2), (13, 2),
+    (17, 3), (25, 3), (33, 4), (49, 4), (65, 5), (97, 5), (129, 6), (193, 6),
+    (257, 7), (385, 7), (513, 8), (769, 8), (1025, 9), (1537, 9), (2049, 10), (3073, 10),
+    (4097, 11), (6145, 11), (8193, 12), (12289, 12), (16385, 13), (24577, 13),
+];
+
+/// How hard the match finder looks for repetitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// How many bytes back a match may start, at most [`MAX_WINDOW`]
+    pub window: usize,
+    /// How many earlier positions with the same prefix are tried for every match
+    pub max_chain: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            window: MAX_WINDOW,
+            max_chain: 64,
+        }
+    }
+}
+
+/// A piece of the parsed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Literal(u8),
+    /// Repeats the `length` bytes starting `distance` bytes back
+    Match {
+        length: u16,
+        distance: u16,
+    },
+}
+
+/// Parses `data` into literals and the longest matches the hash chains lead to
+pub fn tokenize(data: &[u8], options: &Options) -> Vec<Token> {
+    fn hash(data: &[u8], i: usize) -> usize {
+        let prefix = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (prefix.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+    let window = options.window.clamp(1, MAX_WINDOW);
+    // latest position with every hash, and the previous one with the same hash of every position
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let longest = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(data, i)];
+            for _ in 0..options.max_chain {
+                if candidate == usize::MAX || i - candidate > window {
+                    break;
+                }
+                let common = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + longest])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if common > length {
+                    length = common;
+                    distance = i - candidate;
+                    if length == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+            }
+        }
+        if length >= MIN_MATCH {
+            tokens.push(Token::Match {
+                length: length as u16,
+                distance: distance as u16,
+            });
+            for j in i..i + length {
+                insert(j, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            tokens.push(Token::Literal(data[i]));
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Code of `value` in `table`, with its extra bits and their count
+fn code_of(table: &[(u16, u8)], value: u16) -> (u16, u16, u8) {
+    let code = table.partition_point(|&(base, _)| base <= value) - 1;
+    let (base, extra) = table[code];
+    (code as u16, value - base, extra)
+}
+
+/// The literal/length and the distance trees
+#[derive(Debug, Clone)]
+pub struct Trees {
+    pub literals: Tree<u16>,
+    pub distances: Tree<u16>,
+}
+
+impl Trees {
+    /// Builds both trees from the symbol counts of `tokens`
+    pub fn build(tokens: &[Token]) -> Self {
+        let mut literals = HashMap::from([(END, 1)]);
+        let mut distances = HashMap::new();
+        for token in tokens {
+            match *token {
+                Token::Literal(byte) => *literals.entry(byte as u16).or_insert(0) += 1,
+                Token::Match { length, distance } => {
+                    *literals
+                        .entry(257 + code_of(&LENGTHS, length).0)
+                        .or_insert(0) += 1;
+                    *distances
+                        .entry(code_of(&DISTANCES, distance).0)
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+        // a tree needs a letter even when there are no matches
+        if distances.is_empty() {
+            distances.insert(0, 1);
+        }
+        let mut trees = Trees {
+            literals: Tree::build_from_weights(literals),
+            distances: Tree::build_from_weights(distances),
+        };
+        trees.literals.assign_codes();
+        trees.distances.assign_codes();
+        trees
+    }
+    /// The literal/length tree's model as a u32 length and its bytes, then the
+    /// distance tree's, both as in [`huffman_model`][crate::container::huffman_model]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let literals = crate::container::huffman_model(self.literals.as_bin());
+        let mut bytes = (literals.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(literals);
+        bytes.extend(crate::container::huffman_model(self.distances.as_bin()));
+        bytes
+    }
+    /// Reads the trees written by [`to_bytes`][Trees::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid LZSS trees".to_string());
+        let split = bytes
+            .get(..4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize + 4)
+            .filter(|&split| split <= bytes.len())
+            .ok_or_else(invalid)?;
+        Ok(Trees {
+            literals: crate::container::symbol_tree(&bytes[4..split])?,
+            distances: crate::container::symbol_tree(&bytes[split..])?,
+        })
+    }
+}
+
+/// Huffman codes `tokens` followed by the end symbol, returning the bytes and
+/// the number of padding bits in the last one
+pub fn compress(tokens: &[Token], trees: &Trees) -> Result<(Vec<u8>, u8), Error> {
+    let literals = trees.literals.read_codes();
+    let distances = trees.distances.read_codes();
+    let missing = |symbol: u16| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("symbol not found in LZSS trees {}", symbol),
+        )
+    };
+    let mut bits = BitVec::<u8, Msb0>::new();
+    fn push_extra(bits: &mut BitVec<u8, Msb0>, value: u16, count: u8) {
+        for i in (0..count).rev() {
+            bits.push((value >> i) & 1 == 1);
+        }
+    }
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => {
+                let code = literals
+                    .get(&(byte as u16))
+                    .ok_or_else(|| missing(byte as u16))?;
+                bits.extend_from_bitslice(code);
+            }
+            Token::Match { length, distance } => {
+                let (code, extra, count) = code_of(&LENGTHS, length);
+                let symbol = 257 + code;
+                bits.extend_from_bitslice(literals.get(&symbol).ok_or_else(|| missing(symbol))?);
+                push_extra(&mut bits, extra, count);
+                let (code, extra, count) = code_of(&DISTANCES, distance);
+                bits.extend_from_bitslice(distances.get(&code).ok_or_else(|| missing(code))?);
+                push_extra(&mut bits, extra, count);
+            }
+        }
+    }
+    bits.extend_from_bitslice(literals.get(&END).ok_or_else(|| missing(END))?);
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// Decodes the bytes written by [`compress`]
+pub fn decompress(bytes: &[u8], trees: &Trees) -> Result<Vec<u8>, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let mut bits = bytes.view_bits::<Msb0>().iter().by_vals();
+    let truncated = || invalid("LZSS coded data ends without the end symbol");
+    let read_symbol = |tree: &Tree<u16>, bits: &mut dyn Iterator<Item = bool>| {
+        let mut node = tree.root();
+        if !node.has_children() {
+            bits.next().ok_or_else(truncated)?;
+        }
+        while node.has_children() {
+            node = if bits.next().ok_or_else(truncated)? {
+                node.right_child().unwrap()
+            } else {
+                node.left_child().unwrap()
+            };
+        }
+        Ok::<_, Error>(node.letter().unwrap())
+    };
+    let read_extra = |bits: &mut dyn Iterator<Item = bool>, count: u8| {
+        let mut value = 0;
+        for _ in 0..count {
+            value = value << 1 | bits.next().ok_or_else(truncated)? as u16;
+        }
+        Ok::<_, Error>(value)
+    };
+    let mut data = Vec::new();
+    loop {
+        match read_symbol(&trees.literals, &mut bits)? {
+            END => return Ok(data),
+            byte @ 0..=255 => data.push(byte as u8),
+            symbol => {
+                let &(base, count) = LENGTHS
+                    .get(symbol as usize - 257)
+                    .ok_or_else(|| invalid("invalid LZSS length code"))?;
+                let length = (base + read_extra(&mut bits, count)?) as usize;
+                let code = read_symbol(&trees.distances, &mut bits)?;
+                let &(base, count) = DISTANCES
+                    .get(code as usize)
+                    .ok_or_else(|| invalid("invalid LZSS distance code"))?;
+                let distance = (base + read_extra(&mut bits, count)?) as usize;
+                if distance > data.len() {
+                    return Err(invalid("LZSS match reaches before the start of the data"));
+                }
+                // copied a byte at a time, the match may overlap the bytes it produces
+                let start = data.len() - distance;
+                for j in start..start + length {
+                    data.push(data[j]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress, tokenize, Options, Token, Trees};
+
+    fn roundtrip(data: &[u8], options: &Options) -> usize {
+        let tokens = tokenize(data, options);
+        let trees = Trees::from_bytes(&Trees::build(&tokens).to_bytes()).unwrap();
+        let (bytes, _) = compress(&tokens, &trees).unwrap();
+        assert_eq!(decompress(&bytes, &trees).unwrap(), data);
+        bytes.len()
+    }
+
+    #[test]
+    fn roundtrip_without_matches() {
+        for data in [&b""[..], b"a", b"ab", "ünïcödé".as_bytes()] {
+            roundtrip(data, &Options::default());
+        }
+    }
+
+    #[test]
+    fn repetitive_text_compresses() {
+        let line = b"2022-01-15 12:00:00 INFO telemetry packet received\n";
+        let data = line.repeat(200);
+        assert!(roundtrip(&data, &Options::default()) < data.len() / 20);
+    }
+
+    #[test]
+    fn overlapping_and_long_matches() {
+        let data = b"ab".repeat(1000);
+        let tokens = tokenize(&data, &Options::default());
+        assert_eq!(
+            tokens[2],
+            Token::Match {
+                length: 258,
+                distance: 2
+            }
+        );
+        roundtrip(&data, &Options::default());
+    }
+
+    #[test]
+    fn window_limits_distance() {
+        let mut data = b"0123456789abcdef".to_vec();
+        data.extend(b"-".repeat(100));
+        data.extend(b"0123456789abcdef");
+        let options = Options {
+            window: 64,
+            ..Options::default()
+        };
+        assert!(tokenize(&data, &options).iter().all(|token| match token {
+            Token::Match { distance, .. } => *distance <= 64,
+            Token::Literal(_) => true,
+        }));
+        roundtrip(&data, &options);
+    }
+}
@@ -0,0 +1,316 @@
+//! Shannon–Fano, Shannon and Shannon–Fano–Elias codes, the prefix codes that came before Huffman's.
+//!
+//! Every builder gives the same table as [`Tree::read_codes`][crate::huff_encode::Tree::read_codes],
+//! so a letter's code can be compared with its Huffman code. None is shorter on
+//! average: Shannon–Fano splits top-down and may miss the optimum, Shannon's
+//! lengths of `⌈-log2 p⌉` stay below H + 1, and Shannon–Fano–Elias spends one
+//! bit more to do without sorting the letters, staying below H + 2.
+//!
+//! Shannon and Shannon–Fano–Elias codes leave some branches of their tree
+//! unused, so a table is stored as such rather than as a tree.
+use crate::huff_encode::{HuffLetter, HuffLetterAsBytes};
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Code of every letter
+pub type Codes<L = char> = HashMap<L, BitVec<u8, Msb0>>;
+
+/// Longest code a table may hold, Shannon–Fano–Elias needs 62 bits for counts up to 2^60
+pub const MAX_CODE_LEN: usize = 64;
+
+fn push_bits(code: &mut BitVec<u8, Msb0>, value: u128, len: usize) {
+    for i in (0..len).rev() {
+        code.push((value >> i) & 1 == 1);
+    }
+}
+
+/// Letters of a non-zero weight, the heaviest first, ties by letter
+fn by_weight<L: HuffLetter + Ord>(weights: &HashMap<L, usize>) -> Vec<(L, usize)> {
+    let mut letters: Vec<_> = weights
+        .iter()
+        .filter(|&(_, &weight)| weight > 0)
+        .map(|(letter, &weight)| (letter.clone(), weight))
+        .collect();
+    letters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    letters
+}
+
+/// Shannon–Fano codes, splitting the letters sorted by weight where both parts weigh the closest
+pub fn shannon_fano<L: HuffLetter + Ord>(weights: &HashMap<L, usize>) -> Codes<L> {
+    fn split<L: HuffLetter>(letters: &[(L, usize)], code: BitVec<u8, Msb0>, codes: &mut Codes<L>) {
+        if let [(letter, _)] = letters {
+            codes.insert(letter.clone(), code);
+            return;
+        }
+        let total: usize = letters.iter().map(|&(_, weight)| weight).sum();
+        let mut upper = 0;
+        let mut best = (usize::MAX, 1);
+        for (i, (_, weight)) in letters[..letters.len() - 1].iter().enumerate() {
+            upper += weight;
+            let difference = (2 * upper).abs_diff(total);
+            if difference < best.0 {
+                best = (difference, i + 1);
+            }
+        }
+        let (first, second) = letters.split_at(best.1);
+        let mut left = code.clone();
+        left.push(false);
+        split(first, left, codes);
+        let mut right = code;
+        right.push(true);
+        split(second, right, codes);
+    }
+
+    let letters = by_weight(weights);
+    let mut codes = HashMap::new();
+    match letters.as_slice() {
+        [] => {}
+        // a single letter still needs a bit, as in a Huffman tree of one leaf
+        [(letter, _)] => {
+            codes.insert(letter.clone(), bitvec![u8, Msb0; 0]);
+        }
+        _ => split(&letters, BitVec::new(), &mut codes),
+    }
+    codes
+}
+
+/// Shannon codes, the first `⌈-log2 p⌉` bits of the probability of the heavier letters
+pub fn shannon<L: HuffLetter + Ord>(weights: &HashMap<L, usize>) -> Codes<L> {
+    let letters = by_weight(weights);
+    let total = letters
+        .iter()
+        .map(|&(_, weight)| weight as u128)
+        .sum::<u128>();
+    let mut codes = HashMap::new();
+    let mut cumulative = 0;
+    for (letter, weight) in letters {
+        let weight = weight as u128;
+        let mut len = 1;
+        while weight << len < total {
+            len += 1;
+        }
+        let mut code = BitVec::new();
+        push_bits(&mut code, (cumulative << len) / total, len);
+        codes.insert(letter, code);
+        cumulative += weight;
+    }
+    codes
+}
+
+/// Shannon–Fano–Elias codes, the first `⌈-log2 p⌉ + 1` bits of the middle of
+/// the letter's interval, the letters in their own order
+pub fn shannon_fano_elias<L: HuffLetter + Ord>(weights: &HashMap<L, usize>) -> Codes<L> {
+    let mut letters = by_weight(weights);
+    letters.sort_by(|a, b| a.0.cmp(&b.0));
+    let total = letters
+        .iter()
+        .map(|&(_, weight)| weight as u128)
+        .sum::<u128>();
+    let mut codes = HashMap::new();
+    let mut cumulative = 0;
+    for (letter, weight) in letters {
+        let weight = weight as u128;
+        let mut len = 1;
+        while weight << len < total {
+            len += 1;
+        }
+        len += 1;
+        let mut code = BitVec::new();
+        push_bits(
+            &mut code,
+            ((2 * cumulative + weight) << len) / (2 * total),
+            len,
+        );
+        codes.insert(letter, code);
+        cumulative += weight;
+    }
+    codes
+}
+
+/// The table as a u32 count, then every letter's bytes, code length as u8
+/// and code padded to whole bytes
+pub fn to_bytes<L: HuffLetterAsBytes + Ord>(codes: &Codes<L>) -> Vec<u8> {
+    let mut letters: Vec<_> = codes.iter().collect();
+    letters.sort_by(|a, b| a.0.cmp(b.0));
+    let mut bytes = (letters.len() as u32).to_be_bytes().to_vec();
+    for (letter, code) in letters {
+        bytes.extend_from_slice(&letter.as_be_bytes());
+        bytes.push(code.len() as u8);
+        let mut code = code.clone();
+        code.set_uninitialized(false);
+        bytes.extend(code.into_vec());
+    }
+    bytes
+}
+
+/// Reads the table written by [`to_bytes`], which has to be a prefix code
+pub fn from_bytes<L: HuffLetterAsBytes>(bytes: &[u8]) -> Result<Codes<L>, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let too_short = || invalid("code table is too short");
+    let count = bytes
+        .get(..4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .ok_or_else(too_short)?;
+    let letter_len = std::mem::size_of::<L>();
+    let mut at = 4;
+    let mut codes = HashMap::new();
+    for _ in 0..count {
+        let letter = bytes.get(at..at + letter_len).ok_or_else(too_short)?;
+        let letter = L::try_from_be_bytes(letter)
+            .map_err(|err| invalid(&format!("invalid letter in a code table: {}", err)))?;
+        at += letter_len;
+        let len = *bytes.get(at).ok_or_else(too_short)? as usize;
+        if !(1..=MAX_CODE_LEN).contains(&len) {
+            return Err(invalid("code table holds a code of an invalid length"));
+        }
+        let code = bytes
+            .get(at + 1..at + 1 + len.div_ceil(8))
+            .ok_or_else(too_short)?;
+        at += 1 + len.div_ceil(8);
+        codes.insert(letter, code.view_bits::<Msb0>()[..len].to_bitvec());
+    }
+    if at != bytes.len() {
+        return Err(invalid("code table is followed by extra bytes"));
+    }
+    let mut sorted: Vec<_> = codes.values().collect();
+    sorted.sort();
+    if sorted.windows(2).any(|pair| pair[1].starts_with(pair[0])) {
+        return Err(invalid("code table is not a prefix code"));
+    }
+    Ok(codes)
+}
+
+/// Codes `letters` with the table, returning the bytes and the number of padding bits in the last one
+pub fn compress<L: HuffLetter, I: IntoIterator<Item = L>>(
+    letters: I,
+    codes: &Codes<L>,
+) -> Result<(Vec<u8>, u8), Error> {
+    let mut bits = BitVec::<u8, Msb0>::new();
+    for letter in letters {
+        let code = codes.get(&letter).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("letter not found in codes {:?}", letter),
+            )
+        })?;
+        bits.extend_from_bitslice(code);
+    }
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// Decodes the bytes written by [`compress`]
+pub fn decompress<L: HuffLetter>(
+    bytes: &[u8],
+    padding_bits: u8,
+    codes: &Codes<L>,
+) -> Result<Vec<L>, Error> {
+    let decoder: HashMap<_, _> = codes
+        .iter()
+        .map(|(letter, code)| (code.clone(), letter.clone()))
+        .collect();
+    let longest = codes.values().map(|code| code.len()).max().unwrap_or(0);
+    let bits = bytes.view_bits::<Msb0>();
+    let bits = &bits[..bits.len().saturating_sub(padding_bits as usize)];
+    let mut letters = Vec::new();
+    let mut code = BitVec::<u8, Msb0>::new();
+    for bit in bits.iter().by_vals() {
+        code.push(bit);
+        if let Some(letter) = decoder.get(&code) {
+            letters.push(letter.clone());
+            code.clear();
+        } else if code.len() >= longest {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "bits that are no code of the table".to_string(),
+            ));
+        }
+    }
+    if !code.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "prefix coded data ends inside a code".to_string(),
+        ));
+    }
+    Ok(letters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::huff_encode::Tree;
+
+    fn weights() -> HashMap<char, usize> {
+        HashMap::from([('a', 15), ('b', 7), ('c', 6), ('d', 6), ('e', 5)])
+    }
+
+    fn average_len(codes: &Codes, weights: &HashMap<char, usize>) -> f64 {
+        let total: usize = weights.values().sum();
+        weights
+            .iter()
+            .map(|(letter, &weight)| codes[letter].len() as f64 * weight as f64)
+            .sum::<f64>()
+            / total as f64
+    }
+
+    fn lengths(codes: &Codes) -> Vec<usize> {
+        "abcde".chars().map(|letter| codes[&letter].len()).collect()
+    }
+
+    #[test]
+    fn textbook_lengths() {
+        assert_eq!(lengths(&shannon_fano(&weights())), [2, 2, 2, 3, 3]);
+        assert_eq!(lengths(&shannon(&weights())), [2, 3, 3, 3, 3]);
+        assert_eq!(lengths(&shannon_fano_elias(&weights())), [3, 4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn no_shorter_than_huffman() {
+        let weights = crate::huff_encode::build_weights(
+            "it was the best of times, it was the worst of times, it was the age of wisdom",
+        );
+        let mut tree = Tree::build_from_weights(weights.clone());
+        tree.assign_codes();
+        let huffman = average_len(&tree.read_codes(), &weights);
+        let entropy = crate::information::entropy_of_counts(weights.values().copied());
+        for (codes, bound) in [
+            (shannon_fano(&weights), entropy + 1.0),
+            (shannon(&weights), entropy + 1.0),
+            (shannon_fano_elias(&weights), entropy + 2.0),
+        ] {
+            let average = average_len(&codes, &weights);
+            assert!(huffman <= average && average < bound, "{}", average);
+            // prefix codes, whose table is read back
+            assert_eq!(from_bytes::<char>(&to_bytes(&codes)).unwrap(), codes);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = "abracadabra";
+        let weights = crate::huff_encode::build_weights(text);
+        for codes in [
+            shannon_fano(&weights),
+            shannon(&weights),
+            shannon_fano_elias(&weights),
+        ] {
+            let (bytes, padding_bits) = compress(text.chars(), &codes).unwrap();
+            let letters = decompress(&bytes, padding_bits, &codes).unwrap();
+            assert_eq!(letters.into_iter().collect::<String>(), text);
+        }
+        let single = HashMap::from([('x', 3)]);
+        assert_eq!(shannon_fano(&single)[&'x'].len(), 1);
+        assert_eq!(shannon(&single)[&'x'].len(), 1);
+    }
+
+    #[test]
+    fn rejects_codes_that_are_not_prefix() {
+        let mut codes: Codes = HashMap::new();
+        codes.insert('a', bitvec![u8, Msb0; 0]);
+        codes.insert('b', bitvec![u8, Msb0; 0, 1]);
+        assert!(from_bytes::<char>(&to_bytes(&codes)).is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Huffman codes of words, whitespace runs and punctuation, each token taken as one letter.
+//!
+//! Natural language repeats a small vocabulary, so a frequent word costs a
+//! few bits where character Huffman spends some four bits per letter of it. A
+//! token seen fewer than `min_count` times is not worth a dictionary entry: it is
+//! coded as [`ESCAPE`], then spelled out with a tree of characters up to [`SPELL_END`].
+use crate::huff_encode::{HuffLetter, Tree};
+use bitvec::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+/// Token coded before a spelled out one, no token of the text is empty
+pub const ESCAPE: &str = "";
+/// Symbol ending a spelled out token, past every char
+pub const SPELL_END: u32 = 0x11_0000;
+/// Fewest occurrences of a token kept in the dictionary
+pub const DEFAULT_MIN_COUNT: usize = 2;
+/// Longest token in the dictionary, its length is written as u16, longer ones are spelled out
+pub const MAX_TOKEN_LEN: usize = u16::MAX as usize;
+
+#[derive(PartialEq, Eq)]
+enum Class {
+    Word,
+    Space,
+    Other,
+}
+
+fn class(letter: char) -> Class {
+    if letter.is_alphanumeric() {
+        Class::Word
+    } else if letter.is_whitespace() {
+        Class::Space
+    } else {
+        Class::Other
+    }
+}
+
+/// Runs of letters and digits, runs of whitespace, and every other char on its own
+pub fn tokenize(text: &str) -> impl Iterator<Item = &str> + '_ {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let kind = class(first);
+        let len = if kind == Class::Other {
+            first.len_utf8()
+        } else {
+            rest.find(|letter| class(letter) != kind)
+                .unwrap_or(rest.len())
+        };
+        let (token, tail) = rest.split_at(len);
+        rest = tail;
+        Some(token)
+    })
+}
+
+/// Counts of the tokens of `text` seen at least `min_count` times, up to
+/// [`MAX_TOKEN_LEN`] bytes, and of [`ESCAPE`], and counts of the chars spelling
+/// the others and of [`SPELL_END`]
+pub fn build_weights(
+    text: &str,
+    min_count: usize,
+) -> (HashMap<String, usize>, HashMap<u32, usize>) {
+    let mut tokens: HashMap<String, usize> = HashMap::new();
+    for token in tokenize(text) {
+        *tokens.entry(token.to_string()).or_insert(0) += 1;
+    }
+    let mut spelled = HashMap::new();
+    let mut escapes = 0;
+    tokens.retain(|token, &mut count| {
+        if count >= min_count && token.len() <= MAX_TOKEN_LEN {
+            return true;
+        }
+        escapes += count;
+        for letter in token.chars() {
+            *spelled.entry(letter as u32).or_insert(0) += count;
+        }
+        *spelled.entry(SPELL_END).or_insert(0) += count;
+        false
+    });
+    if escapes > 0 {
+        tokens.insert(ESCAPE.to_string(), escapes);
+    }
+    (tokens, spelled)
+}
+
+/// Huffman trees of the dictionary of tokens and of the chars of the tokens spelled out
+#[derive(Debug, Clone)]
+pub struct TokenTrees {
+    /// `None` when the text has no tokens, it is empty
+    pub tokens: Option<Tree<String>>,
+    /// `None` when every token is in the dictionary
+    pub spelled: Option<Tree<u32>>,
+}
+
+fn build_tree<L: HuffLetter>(weights: HashMap<L, usize>) -> Tree<L> {
+    let mut tree = Tree::build_from_weights(weights);
+    tree.assign_codes();
+    tree
+}
+
+impl TokenTrees {
+    /// Builds the trees of the tokens of `text`, an empty text gets no trees
+    /// at all and codes to nothing
+    pub fn build(text: &str, min_count: usize) -> Result<Self, Error> {
+        if min_count == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "min count needs to be at least 1".to_string(),
+            ));
+        }
+        let (tokens, spelled) = build_weights(text, min_count);
+        Ok(TokenTrees {
+            tokens: (!tokens.is_empty()).then(|| build_tree(tokens)),
+            spelled: (!spelled.is_empty()).then(|| build_tree(spelled)),
+        })
+    }
+    /// Codes of the tokens in the dictionary, none without a tree
+    pub fn token_codes(&self) -> HashMap<String, BitVec<u8, Msb0>> {
+        self.tokens
+            .as_ref()
+            .map(|tree| tree.read_codes())
+            .unwrap_or_default()
+    }
+    /// The dictionary as a u32 count and every token as a u16 length and its
+    /// UTF-8 bytes, a u32 length of the tree of their indices as in
+    /// [`huffman_model`][crate::container::huffman_model] and that tree, then
+    /// the tree of the spelled chars in the same way, if there is one. Without
+    /// tokens it is only a count and a length of 0
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut tokens: Vec<String> = self.token_codes().into_keys().collect();
+        tokens.sort();
+        let indices: HashMap<&String, u32> = tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| (token, i as u32))
+            .collect();
+        let mut bytes = (tokens.len() as u32).to_be_bytes().to_vec();
+        for token in &tokens {
+            bytes.extend_from_slice(&(token.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(token.as_bytes());
+        }
+        let tree = match &self.tokens {
+            Some(tree) => crate::container::huffman_model(
+                tree.map_letters(&mut |token| indices[token]).as_bin(),
+            ),
+            None => Vec::new(),
+        };
+        bytes.extend_from_slice(&(tree.len() as u32).to_be_bytes());
+        bytes.extend(tree);
+        if let Some(spelled) = &self.spelled {
+            bytes.extend(crate::container::huffman_model(spelled.as_bin()));
+        }
+        bytes
+    }
+    /// Reads the trees written by [`to_bytes`][TokenTrees::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid token trees".to_string());
+        let read_u32 = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(invalid)
+        };
+        let count = read_u32(0)?;
+        let mut at = 4;
+        let mut tokens = Vec::new();
+        for _ in 0..count {
+            let len = bytes
+                .get(at..at + 2)
+                .map(|b| u16::from_be_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(invalid)?;
+            let token = bytes.get(at + 2..at + 2 + len).ok_or_else(invalid)?;
+            tokens.push(String::from_utf8(token.to_vec()).map_err(|_| invalid())?);
+            at += 2 + len;
+        }
+        let len = read_u32(at)?;
+        let tree = bytes.get(at + 4..at + 4 + len).ok_or_else(invalid)?;
+        let spelled = match &bytes[at + 4 + len..] {
+            [] => None,
+            spelled => Some(crate::container::symbol_tree::<u32>(spelled)?),
+        };
+        if tree.is_empty() {
+            if count != 0 || spelled.is_some() {
+                return Err(invalid());
+            }
+            return Ok(TokenTrees {
+                tokens: None,
+                spelled,
+            });
+        }
+        let tree = crate::container::symbol_tree::<u32>(tree)?;
+        if tree
+            .read_codes()
+            .keys()
+            .any(|&index| index as usize >= tokens.len())
+        {
+            return Err(invalid());
+        }
+        let tokens = tree.map_letters(&mut |&index| tokens[index as usize].clone());
+        if tokens.read_codes().contains_key(ESCAPE) != spelled.is_some() {
+            return Err(invalid());
+        }
+        Ok(TokenTrees {
+            tokens: Some(tokens),
+            spelled,
+        })
+    }
+}
+
+/// Codes the tokens of `text`, spelling out those not in the dictionary,
+/// returning the bytes and the number of padding bits in the last one
+pub fn compress(text: &str, trees: &TokenTrees) -> Result<(Vec<u8>, u8), Error> {
+    let tokens = trees.token_codes();
+    let spelled = trees
+        .spelled
+        .as_ref()
+        .map(|tree| tree.read_codes())
+        .unwrap_or_default();
+    let not_found = |token: &str| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("token not found in codes {:?}", token),
+        )
+    };
+    let mut bits = BitVec::<u8, Msb0>::new();
+    for token in tokenize(text) {
+        if let Some(code) = tokens.get(token) {
+            bits.extend_from_bitslice(code);
+            continue;
+        }
+        bits.extend_from_bitslice(tokens.get(ESCAPE).ok_or_else(|| not_found(token))?);
+        for letter in token.chars().map(|letter| letter as u32).chain([SPELL_END]) {
+            bits.extend_from_bitslice(spelled.get(&letter).ok_or_else(|| not_found(token))?);
+        }
+    }
+    let padding_bits = crate::huff_encode::calc_padding_bits(bits.len());
+    Ok((bits.into_vec(), padding_bits))
+}
+
+/// The letter of the next code of `bits`, `None` when they end first
+fn decode<L: HuffLetter>(tree: &Tree<L>, bits: &mut impl Iterator<Item = bool>) -> Option<L> {
+    let mut node = tree.root();
+    if !node.has_children() {
+        bits.next()?;
+    }
+    while node.has_children() {
+        node = if bits.next()? {
+            node.right_child()?
+        } else {
+            node.left_child()?
+        };
+    }
+    node.letter()
+}
+
+/// Decodes the bytes written by [`compress`]
+pub fn decompress(bytes: &[u8], padding_bits: u8, trees: &TokenTrees) -> Result<String, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    let bits = bytes.view_bits::<Msb0>();
+    let mut bits = bits[..bits.len().saturating_sub(padding_bits as usize)]
+        .iter()
+        .by_vals()
+        .peekable();
+    let mut text = String::new();
+    while bits.peek().is_some() {
+        let tokens = trees
+            .tokens
+            .as_ref()
+            .ok_or_else(|| invalid("token coded data without a tree of tokens"))?;
+        let token = decode(tokens, &mut bits)
+            .ok_or_else(|| invalid("token coded data ends inside a code"))?;
+        if token != ESCAPE {
+            text.push_str(&token);
+            continue;
+        }
+        let spelled = trees
+            .spelled
+            .as_ref()
+            .ok_or_else(|| invalid("escape without a tree of spelled chars"))?;
+        loop {
+            let letter = decode(spelled, &mut bits)
+                .ok_or_else(|| invalid("token coded data ends inside a spelled token"))?;
+            if letter == SPELL_END {
+                break;
+            }
+            text.push(char::from_u32(letter).ok_or_else(|| invalid("invalid spelled char"))?);
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words_spaces_and_punctuation() {
+        assert_eq!(
+            tokenize("Zażółć gęślą,  jaźń!?\n42").collect::<Vec<_>>(),
+            [
+                "Zażółć",
+                " ",
+                "gęślą",
+                ",",
+                "  ",
+                "jaźń",
+                "!",
+                "?",
+                "\n",
+                "42"
+            ]
+        );
+        assert_eq!(tokenize("").count(), 0);
+    }
+
+    #[test]
+    fn roundtrip_with_escapes() {
+        let text = "the cat and the hat, the bat and a rat; zebra!";
+        for min_count in [1, 2, 3, 100] {
+            let trees = TokenTrees::build(text, min_count).unwrap();
+            let trees = TokenTrees::from_bytes(&trees.to_bytes()).unwrap();
+            assert_eq!(trees.spelled.is_some(), min_count > 1);
+            let (bytes, padding_bits) = compress(text, &trees).unwrap();
+            assert_eq!(decompress(&bytes, padding_bits, &trees).unwrap(), text);
+        }
+        // spelled out with the chars of the only token
+        let trees = TokenTrees::build("word", 2).unwrap();
+        assert!(compress("drow", &trees).is_ok());
+        assert!(compress("drow", &TokenTrees::build("word", 1).unwrap()).is_err());
+        assert!(TokenTrees::build("word", 0).is_err());
+    }
+
+    #[test]
+    fn beats_character_huffman() {
+        let text = "it was the best of times, it was the worst of times, it was the age of \
+                    wisdom, it was the age of foolishness, it was the epoch of belief. "
+            .repeat(20);
+        let (bytes, _) = compress(&text, &TokenTrees::build(&text, 2).unwrap()).unwrap();
+        let weights = crate::huff_encode::build_weights(&text);
+        let mut tree = Tree::build_from_weights(weights.clone());
+        tree.assign_codes();
+        let codes = tree.read_codes();
+        let bits: usize = weights.iter().map(|(c, &n)| codes[c].len() * n).sum();
+        assert!(bytes.len() * 8 * 2 < bits);
+    }
+
+    #[test]
+    fn empty_text_has_no_trees() {
+        let trees = TokenTrees::build("", 2).unwrap();
+        assert!(trees.tokens.is_none() && trees.spelled.is_none());
+        let trees = TokenTrees::from_bytes(&trees.to_bytes()).unwrap();
+        assert!(trees.tokens.is_none());
+        let (bytes, padding_bits) = compress("", &trees).unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(decompress(&bytes, padding_bits, &trees).unwrap(), "");
+        assert!(compress("word", &trees).is_err());
+        assert!(decompress(&[0x80], 7, &trees).is_err());
+    }
+}
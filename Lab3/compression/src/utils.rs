@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{prelude::*, BufReader, BufWriter, IoSlice, SeekFrom},
+    io::{prelude::*, BufReader, BufWriter, SeekFrom},
     io::{Error, ErrorKind},
 };
 
@@ -60,7 +60,7 @@ pub fn encode(mut input: File, mut output: File) -> std::io::Result<()> {
         // Clear buffer for next block
         buffer = [0; 7];
     }
-    output.write(output_bytes.as_slice())?;
+    output.write_all(output_bytes.as_slice())?;
     std::fs::remove_file("tmp")?;
     Ok(())
 }
@@ -131,7 +131,7 @@ fn offset_bytes(bytes: &[u8], n: usize) -> Vec<u8> {
 
     offset_bytes
 }
-pub fn decode(mut input: File, mut output: File, repair: bool) -> std::io::Result<()> {
+pub fn decode(mut input: File, output: File, repair: bool) -> std::io::Result<()> {
     let mut buffer = [0; 8];
     let mut error_count = 0;
     let mut block_count = 0;
@@ -162,7 +162,7 @@ pub fn decode(mut input: File, mut output: File, repair: bool) -> std::io::Resul
     println!("Error Count: {}", error_count);
     if repair {
         let mut tmp = File::create("tmp")?;
-        tmp.write(output_bytes.as_slice())?;
+        tmp.write_all(output_bytes.as_slice())?;
     }
     if repair {
         // read from src file
@@ -18,8 +18,8 @@ struct Opts {
 }
 fn main() -> std::io::Result<()> {
     let opts = Opts::parse();
-    let mut input = File::open(opts.input)?;
-    let mut output = File::create(opts.output)?;
+    let input = File::open(opts.input)?;
+    let output = File::create(opts.output)?;
     decode(input, output, opts.repair)?;
     Ok(())
 }
@@ -35,9 +35,9 @@ fn main() -> std::io::Result<()> {
 
             // Toggle that specific bit
             original ^= mask;
-            output.write(&original.to_be_bytes())?;
+            output.write_all(&original.to_be_bytes())?;
         } else {
-            output.write(&original.to_be_bytes())?;
+            output.write_all(&original.to_be_bytes())?;
         }
         //println!("{:?}", buffer);
 